@@ -4,36 +4,289 @@ use std::collections::HashMap;
 
 use crate::logging::Log;
 
-use super::{OpSize, OpType, value::Value, constants::*};
+use super::{CpuType, OpSize, OpType, float, value::Value, constants::*};
 
 #[derive(Debug, PartialEq)]
 pub enum ControlRegister {
     Sfc, Dfc, Usp, Vbr, //68010 also put usp here?
+    Cacr, Caar, Msp, Isp,
+    Tc, Itt0, Itt1, Dtt0, Dtt1, Mmusr, Urp, Srp,
+    Buscr, Pcr,
+    Crp, Tt0, Tt1, // 68030 MMU registers, only reachable with pmove
+    Acr0, Acr1, Acr2, Acr3, Rambar0, Rambar1, Mbar, // ColdFire
 }
 
 impl ControlRegister {
-    pub fn format(&self) -> u16 {
+    /// The MOVEC register field, if the CPU has this register
+    pub fn format(&self, cpu_type: &CpuType) -> Result<u16, Log> {
+        use CpuType::*;
+
+        let coldfire = cpu_type.is_coldfire();
+
+        let (format, valid) = match self {
+            ControlRegister::Sfc   => (0x000, !coldfire),
+            ControlRegister::Dfc   => (0x001, !coldfire),
+            ControlRegister::Usp   => (0x800, true),
+            ControlRegister::Vbr   => (0x801, true),
+            ControlRegister::Cacr  => (0x002, !matches!(cpu_type, MC68000 | MC68010 | CPU32)),
+            ControlRegister::Caar  => (0x802, matches!(cpu_type, MC68020 | MC68030)),
+            ControlRegister::Msp   => (0x803, matches!(cpu_type, MC68020 | MC68030 | MC68040)),
+            ControlRegister::Isp   => (0x804, matches!(cpu_type, MC68020 | MC68030 | MC68040)),
+            ControlRegister::Tc    => (0x003, matches!(cpu_type, MC68040 | MC68060)),
+            ControlRegister::Itt0  => (0x004, matches!(cpu_type, MC68040 | MC68060)),
+            ControlRegister::Itt1  => (0x005, matches!(cpu_type, MC68040 | MC68060)),
+            ControlRegister::Dtt0  => (0x006, matches!(cpu_type, MC68040 | MC68060)),
+            ControlRegister::Dtt1  => (0x007, matches!(cpu_type, MC68040 | MC68060)),
+            ControlRegister::Mmusr => (0x805, matches!(cpu_type, MC68040)),
+            ControlRegister::Urp   => (0x806, matches!(cpu_type, MC68040 | MC68060)),
+            ControlRegister::Srp   => (0x807, matches!(cpu_type, MC68040 | MC68060)),
+            ControlRegister::Buscr => (0x008, matches!(cpu_type, MC68060)),
+            ControlRegister::Pcr   => (0x808, matches!(cpu_type, MC68060)),
+            ControlRegister::Crp | ControlRegister::Tt0 | ControlRegister::Tt1 => (0, false),
+            ControlRegister::Acr0  => (0x004, coldfire),
+            ControlRegister::Acr1  => (0x005, coldfire),
+            ControlRegister::Acr2  => (0x006, coldfire),
+            ControlRegister::Acr3  => (0x007, coldfire),
+            ControlRegister::Rambar0 => (0xC04, coldfire),
+            ControlRegister::Rambar1 => (0xC05, coldfire),
+            ControlRegister::Mbar  => (0xC0F, coldfire),
+        };
+
+        match valid {
+            true  => Ok(format),
+            false => Err(Log::UnsupportedControlRegister),
+        }
+    }
+
+    /// The 68030 PMOVE register field and the size of the register, or None if pmove can't access it
+    pub fn pmmu_format(&self) -> Option<(u16, OpSize)> {
         match self {
-            ControlRegister::Sfc => 0x000,
-            ControlRegister::Dfc => 0x001,
-            ControlRegister::Usp => 0x800,
-            ControlRegister::Vbr => 0x801,
+            ControlRegister::Tc    => Some((0b010_000 << 10, OpSize::L)),
+            ControlRegister::Srp   => Some((0b010_010 << 10, OpSize::Unsized)), // 64 bits
+            ControlRegister::Crp   => Some((0b010_011 << 10, OpSize::Unsized)),
+            ControlRegister::Mmusr => Some((0b011_000 << 10, OpSize::W)),
+            ControlRegister::Tt0   => Some((0b000_010 << 10, OpSize::L)),
+            ControlRegister::Tt1   => Some((0b000_011 << 10, OpSize::L)),
+            _ => None,
         }
     }
+
+    /// Registers beyond SFC, DFC and VBR, which are only recognized by the instructions that use them
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "CACR"  => ControlRegister::Cacr,
+            "CAAR"  => ControlRegister::Caar,
+            "MSP"   => ControlRegister::Msp,
+            "ISP"   => ControlRegister::Isp,
+            "TC"    => ControlRegister::Tc,
+            "ITT0"  => ControlRegister::Itt0,
+            "ITT1"  => ControlRegister::Itt1,
+            "DTT0"  => ControlRegister::Dtt0,
+            "DTT1"  => ControlRegister::Dtt1,
+            "MMUSR" | "PSR" => ControlRegister::Mmusr,
+            "URP"   => ControlRegister::Urp,
+            "SRP"   => ControlRegister::Srp,
+            "BUSCR" => ControlRegister::Buscr,
+            "PCR"   => ControlRegister::Pcr,
+            "CRP"   => ControlRegister::Crp,
+            "TT0"   => ControlRegister::Tt0,
+            "TT1"   => ControlRegister::Tt1,
+            "ACR0"  => ControlRegister::Acr0,
+            "ACR1"  => ControlRegister::Acr1,
+            "ACR2"  => ControlRegister::Acr2,
+            "ACR3"  => ControlRegister::Acr3,
+            "RAMBAR0" => ControlRegister::Rambar0,
+            "RAMBAR1" | "RAMBAR" => ControlRegister::Rambar1,
+            "MBAR"  => ControlRegister::Mbar,
+            _ => return None,
+        })
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RegType {
     Dn,
     An,
 }
 
+/// D/A, reg, W/L, scale
+pub type Index = (RegType, u8, bool, u8);
+
 #[derive(Debug, PartialEq)]
 pub enum ExtensionWord {
     // D/A, reg, W/L, scale, displacement
     Brief(RegType, u8, bool, u8, Value),
-    // D/A, reg, W/L, scale, BS, IS, BD size, I/IS
-    Full(RegType, u8, bool, u8, bool, bool, u8, u8),
+    // index or None if suppressed, base suppressed, base displacement, memory indirection
+    Full(Option<Index>, bool, Displacement, MemoryIndirect),
+}
+
+/// Base or outer displacement of a full extension word
+#[derive(Debug, PartialEq)]
+pub enum Displacement {
+    Null,
+    Word(Value),
+    Long(Value),
+    Auto(OpSize, Value), // no size given, word or long is picked once labels are known
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MemoryIndirect {
+    None,
+    PreIndexed(Displacement),  // ([bd, An, Xn], od)
+    PostIndexed(Displacement), // ([bd, An], Xn, od)
+}
+
+impl ExtensionWord {
+    /// Size in bytes, including displacements
+    fn len(&self) -> u8 {
+        match self {
+            Self::Brief(_, _, _, _, _) => 2,
+
+            Self::Full(_, _, bd, indirect) => match indirect {
+                MemoryIndirect::None => 2 + bd.len(),
+                MemoryIndirect::PreIndexed(od) | MemoryIndirect::PostIndexed(od) => 2 + bd.len() + od.len(),
+            },
+        }
+    }
+
+    /// Brief extension words become full ones when the displacement doesn't fit in 8 bits,
+    /// and full displacements without a size grow from word to long. Returns true if the size changed
+    fn grow(&mut self, resolve_bd: &dyn Fn(&Value) -> Result<i64, Log>, resolve_od: &dyn Fn(&Value) -> Result<i64, Log>, extended_addressing: bool) -> bool {
+        match self {
+            Self::Brief(reg_type, reg, size, scale, disp) => {
+                if !extended_addressing || !matches!(resolve_bd(disp), Ok(disp) if signed_fits(disp, 8).is_err()) {
+                    return false;
+                }
+
+                let disp = std::mem::replace(disp, Value::Number(0));
+                *self = Self::Full(Some((*reg_type, *reg, *size, *scale)), false, Displacement::Auto(OpSize::W, disp), MemoryIndirect::None);
+                true
+            }
+
+            Self::Full(_, _, bd, indirect) => {
+                let grown = bd.grow(resolve_bd);
+
+                match indirect {
+                    MemoryIndirect::None => grown,
+                    MemoryIndirect::PreIndexed(od) | MemoryIndirect::PostIndexed(od) => od.grow(resolve_od) || grown,
+                }
+            }
+        }
+    }
+
+    fn encode(&self, labels: &HashMap<String, u32>, defines: &HashMap<String, Value>, location: u32, pc_relative: bool) -> Result<Vec<u16>, Log> {
+        match self {
+            Self::Brief(reg_type, reg, size, scale, disp) => {
+                let disp = signed_fits(displacement(disp, labels, defines, location, pc_relative)?, 8)?;
+                Ok(vec![(reg_type.value() << 15) | ((*reg as u16) << 12) | ((*size as u16) << 11) | ((*scale as u16) << 9) | ((disp as u16) & 0xFF)])
+            }
+
+            Self::Full(index, base_suppressed, bd, indirect) => {
+                let absolute = |value: &Value| -> Result<i64, Log> {
+                    Ok(fits(value.resolve_value(labels, defines)?, 32)? as u32 as i32 as i64)
+                };
+
+                // without a base register, the base displacement is an address rather than relative to it
+                let bd_value = match (bd.value(), base_suppressed) {
+                    (None, _) => 0,
+                    (Some(bd), true) => absolute(bd)?,
+                    (Some(bd), false) => displacement(bd, labels, defines, location, pc_relative)?,
+                };
+
+                let od_value = match indirect {
+                    MemoryIndirect::PreIndexed(od) | MemoryIndirect::PostIndexed(od) => od.value().map_or(Ok(0), absolute)?,
+                    MemoryIndirect::None => 0,
+                };
+
+                Self::full(index, *base_suppressed, bd, indirect, bd_value, od_value)
+            }
+        }
+    }
+
+    /// The full extension word followed by the base and outer displacements
+    fn full(index: &Option<Index>, base_suppressed: bool, bd: &Displacement, indirect: &MemoryIndirect, bd_value: i64, od_value: i64) -> Result<Vec<u16>, Log> {
+        let index = match index {
+            Some((reg_type, reg, size, scale)) => (reg_type.value() << 15) | ((*reg as u16) << 12) | ((*size as u16) << 11) | ((*scale as u16) << 9),
+            None => 1 << 6,
+        };
+
+        let (od, indirection) = match indirect {
+            MemoryIndirect::None => (&Displacement::Null, 0),
+            MemoryIndirect::PreIndexed(od) => (od, od.size_field()),
+            MemoryIndirect::PostIndexed(od) => (od, 0b100 | od.size_field()),
+        };
+
+        let mut words = vec![index | (1 << 8) | ((base_suppressed as u16) << 7) | (bd.size_field() << 4) | indirection];
+        words.extend(bd.words(bd_value)?);
+
+        if !matches!(indirect, MemoryIndirect::None) {
+            words.extend(od.words(od_value)?);
+        }
+
+        Ok(words)
+    }
+}
+
+impl Displacement {
+    fn new(text: &str, last_label: &str) -> Result<Self, Log> {
+        Ok(if let Some(word) = text.strip_suffix(".w") {
+            Self::Word(Value::new(word, last_label)?)
+        } else if let Some(long) = text.strip_suffix(".l") {
+            Self::Long(Value::new(long, last_label)?)
+        } else {
+            Self::Auto(OpSize::W, Value::new(text, last_label)?) //start out as a word, grown if needed once labels are known
+        })
+    }
+
+    fn value(&self) -> Option<&Value> {
+        match self {
+            Self::Null => None,
+            Self::Word(value) | Self::Long(value) | Self::Auto(_, value) => Some(value),
+        }
+    }
+
+    fn len(&self) -> u8 {
+        match self {
+            Self::Null => 0,
+            Self::Word(_) | Self::Auto(OpSize::W, _) => 2,
+            Self::Long(_) | Self::Auto(_, _) => 4,
+        }
+    }
+
+    /// Size field of the extension word, 1 for null, 2 for word and 3 for long
+    fn size_field(&self) -> u16 {
+        match self.len() {
+            0 => 0b01,
+            2 => 0b10,
+            _ => 0b11,
+        }
+    }
+
+    fn words(&self, value: i64) -> Result<Vec<u16>, Log> {
+        Ok(match self.len() {
+            0 => vec![],
+            2 => vec![signed_fits(value, 16)? as u16],
+            _ => {
+                let value = fits(value as u64, 32)?;
+                vec![(value >> 16) as u16, value as u16]
+            }
+        })
+    }
+
+    fn grow(&mut self, resolve: &dyn Fn(&Value) -> Result<i64, Log>) -> bool {
+        let Self::Auto(size @ OpSize::W, value) = self else {
+            return false;
+        };
+
+        match resolve(value) {
+            Ok(value) if signed_fits(value, 16).is_err() => {
+                *size = OpSize::L;
+                true
+            }
+
+            _ => false,
+        }
+    }
 }
 
 impl RegType {
@@ -58,6 +311,7 @@ pub enum AddressingMode {
     PCIndex(ExtensionWord),
     AbsoluteShort(Value),
     AbsoluteLong(Value),
+    Absolute(OpSize, Value), // no size given, short or long is picked once labels are known
     Immediate(OpSize, Value),
 
     BranchDisplacement(OpSize, Value),
@@ -70,33 +324,113 @@ pub enum AddressingMode {
 
     ControlReg(ControlRegister),
 
+    RegisterPair(u8, u8),  // Dh:Dl, data registers only
+    IndirectPair(u8, u8),  // (Rn):(Rn), 0-7 are data registers and 8-15 address registers
+    Bitfield(BitfieldPart, BitfieldPart), // {offset:width}
+    Caches(u8), // cache selection of cinv and cpush: NC, DC, IC or BC
+
+    FloatRegister(u8),
+    FloatPair(u8, u8),     // FPc:FPs of fsincos
+    FloatRegisterList(u8), // bit n is FPn
+    FloatControl(u8),      // FPCR, FPSR and FPIAR as bits 2, 1 and 0
+
+    MacPair(u8, u8, u8),     // Ry and Rx of mac as 0-15 with bit 4 set for the upper word, and the scale factor
+    MacLoad(u8, u8, bool),   // Rw and the accumulator of mac with load, and whether the loaded value is masked
+    Accumulator(u8),         // ACC0-ACC3
+    MacControl(u8),          // MACSR, ACCEXT01, MASK and ACCEXT23 as 0b100 to 0b111
+
     Empty,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum BitfieldPart {
+    Register(u8),
+    Value(Value),
+}
+
 impl AddressingMode {
-    pub fn ea_size(modes: &[Self; 2]) -> u8 {
-        let mut count = 0;
+    pub fn ea_size(modes: &[Self]) -> u8 {
+        modes.iter().map(|mode| mode.size()).sum()
+    }
 
-        for mode in modes {
-            count += match mode {
-                Self::AddressDisplacement(_, _) | Self::AddressIndex(_, _) |
-                Self::PCDisplacement(_)         | Self::PCIndex(_) |
-                Self::AbsoluteShort(_)          | Self::RegisterList(_) => 2,
+    /// Size of the extension words in bytes
+    pub fn size(&self) -> u8 {
+        match self {
+            Self::AddressIndex(ext_word, _) | Self::PCIndex(ext_word) => ext_word.len(),
 
-                Self::AbsoluteLong(_) => 4,
+            Self::AddressDisplacement(_, _) | Self::PCDisplacement(_) |
+            Self::AbsoluteShort(_)          | Self::RegisterList(_) |
+            Self::Absolute(OpSize::W, _) => 2,
 
-                Self::Immediate(size, _) => match size {
-                    OpSize::L => 4,
-                    _ => 2,
-                },
+            Self::AbsoluteLong(_) | Self::Absolute(_, _) => 4,
 
-                Self::BranchDisplacement(OpSize::W, _) => 2,
+            Self::Immediate(size, _) => match size {
+                OpSize::L | OpSize::S => 4,
+                OpSize::D => 8,
+                OpSize::X | OpSize::P => 12,
+                _ => 2,
+            },
 
-                _ => 0,
-            };
+            Self::BranchDisplacement(OpSize::W, _) => 2,
+            Self::BranchDisplacement(OpSize::L, _) => 4,
+
+            _ => 0,
         }
+    }
+
+    /// Grows absolute addresses and displacements without a size that turn out not to fit once labels are known.
+    /// On the 68020 and up, displacements too large for the brief formats switch to a full extension word.
+    /// Returns true if the size changed
+    pub fn grow(&mut self, labels: &HashMap<String, u32>, defines: &HashMap<String, Value>, location: u32, extended_addressing: bool) -> bool {
+        let pc_relative = matches!(self, Self::PCDisplacement(_) | Self::PCIndex(_));
+        let relative = |disp: &Value| displacement(disp, labels, defines, location, pc_relative);
+        let absolute = |value: &Value| Ok(fits(value.resolve_value(labels, defines)?, 32)? as u32 as i32 as i64);
+
+        match self {
+            Self::Absolute(size @ OpSize::W, addr) => match addr.resolve_value(labels, defines) {
+                Ok(addr) if !absolute_short_fits(addr) => {
+                    *size = OpSize::L;
+                    true
+                }
 
-        count
+                _ => false,
+            },
+
+            Self::AddressDisplacement(disp, _) | Self::PCDisplacement(disp) => {
+                if !extended_addressing || !matches!(relative(disp), Ok(disp) if signed_fits(disp, 16).is_err()) {
+                    return false;
+                }
+
+                let ext_word = ExtensionWord::Full(None, false, Displacement::Auto(OpSize::L, std::mem::replace(disp, Value::Number(0))), MemoryIndirect::None);
+
+                *self = match self {
+                    Self::AddressDisplacement(_, reg) => Self::AddressIndex(ext_word, *reg),
+                    _ => Self::PCIndex(ext_word),
+                };
+
+                true
+            }
+
+            Self::AddressIndex(ext_word, _) | Self::PCIndex(ext_word) => match ext_word {
+                ExtensionWord::Full(_, true, _, _) => ext_word.grow(&absolute, &absolute, extended_addressing),
+                _ => ext_word.grow(&relative, &absolute, extended_addressing),
+            },
+
+            _ => false,
+        }
+    }
+
+    /// Modes using a full extension word, which CPU32 lacks
+    pub fn is_full_format(&self) -> bool {
+        matches!(self, Self::AddressIndex(ExtensionWord::Full(..), _) | Self::PCIndex(ExtensionWord::Full(..)))
+    }
+
+    /// ColdFire indexes with a long register scaled by 1, 2 or 4, and has no full extension words
+    pub fn is_coldfire(&self) -> bool {
+        match self {
+            Self::AddressIndex(ext_word, _) | Self::PCIndex(ext_word) => matches!(ext_word, ExtensionWord::Brief(_, _, true, 0..=2, _)),
+            _ => true,
+        }
     }
 
     pub fn mask_bit(&self) -> u32 {
@@ -112,6 +446,8 @@ impl AddressingMode {
             Self::PCIndex(_)                  => 0b0_000_000_000100000000,
             Self::AbsoluteShort(_)            => 0b0_000_000_001000000000,
             Self::AbsoluteLong(_)             => 0b0_000_000_010000000000,
+            Self::Absolute(OpSize::W, _)      => 0b0_000_000_001000000000,
+            Self::Absolute(_, _)              => 0b0_000_000_010000000000,
             Self::Immediate(_, _)             => 0b0_000_000_100000000000,
             Self::BranchDisplacement(_, _)    => 0b0_000_001_000000000000,
             Self::RegisterList(_)             => 0b0_000_010_000000000000,
@@ -120,11 +456,23 @@ impl AddressingMode {
             Self::SR                          => 0b0_010_000_000000000000,
             Self::USP                         => 0b0_100_000_000000000000,
             Self::ControlReg(_)               => 0b1_000_000_000000000000,
+            Self::RegisterPair(_, _)          => 0b001_0_000_000_000000000000,
+            Self::IndirectPair(_, _)          => 0b010_0_000_000_000000000000,
+            Self::Bitfield(_, _)              => 0b100_0_000_000_000000000000,
+            Self::Caches(_)                   => 0b1000_0_000_000_000000000000,
+            Self::FloatRegister(_)            => 0b1_0000_0_000_000_000000000000,
+            Self::FloatPair(_, _)             => 0b10_0000_0_000_000_000000000000,
+            Self::FloatRegisterList(_)        => 0b100_0000_0_000_000_000000000000,
+            Self::FloatControl(_)             => 0b1000_0000_0_000_000_000000000000,
+            Self::MacPair(_, _, _)            => 0b1_0000_0000_0_000_000_000000000000,
+            Self::Accumulator(_)              => 0b10_0000_0000_0_000_000_000000000000,
+            Self::MacLoad(_, _, _)            => 0b100_0000_0000_0_000_000_000000000000,
+            Self::MacControl(_)               => 0b1000_0000_0000_0_000_000_000000000000,
             Self::Empty                       => 0b0_000_000_000000000000,
         }
     }
 
-    pub fn effective_addressing(&self, labels: &HashMap<String, u32>, defines: &HashMap<String, u64>, location: u32) -> Result<(u16, Vec<u16>), Log> {
+    pub fn effective_addressing(&self, labels: &HashMap<String, u32>, defines: &HashMap<String, Value>, location: u32) -> Result<(u16, Vec<u16>), Log> {
         let ea = match self {
             Self::DataRegister(reg) => (0b000, *reg, vec![]),
             Self::AddressRegister(reg) => (0b001, *reg, vec![]),
@@ -134,76 +482,46 @@ impl AddressingMode {
             Self::AddressPredecrement(reg) => (0b100, *reg, vec![]),
 
             Self::AddressDisplacement(disp, reg) => {
-                let mut disp2 = disp.resolve_value(labels, defines)? as i16;
-                if let Value::Label(_) = disp {
-                    disp2 -= location as i16 + 2;
-                }
-
+                let disp2 = signed_fits(displacement(disp, labels, defines, location, false)?, 16)?;
                 (0b101, *reg, vec![disp2 as u16])
             }
 
-            Self::AddressIndex(ext_word, an) => {
-                match ext_word {
-                    ExtensionWord::Brief(reg_type, reg, size, scale, displacement) => {
-                        //todo: check if disp is out of range?
-                        let mut disp2 = displacement.resolve_value(labels, defines)? as i16;
-                        if let Value::Label(_) = displacement {
-                            disp2 -= location as i16 + 2;
-                        }
-
-                        (
-                            0b110,
-                            *an,
-                            vec![(reg_type.value() << 15) | ((*reg as u16) << 12) | ((*size as u16) << 11) | ((*scale as u16) << 9) | ((disp2 as u16) & 0xFF)],
-                        )
-                    }
-
-                    ExtensionWord::Full(_, _, _, _, _, _, _, _) => todo!(),
-                }
-            }
+            Self::AddressIndex(ext_word, an) => (0b110, *an, ext_word.encode(labels, defines, location, false)?),
 
             Self::PCDisplacement(disp) => {
-                let mut disp2 = disp.resolve_value(labels, defines)? as i16;
-                if let Value::Label(_) = disp {
-                    disp2 -= location as i16 + 2;
-                }
-
+                let disp2 = signed_fits(displacement(disp, labels, defines, location, true)?, 16)?;
                 (0b111, 0b010, vec![disp2 as u16])
             }
 
-            Self::PCIndex(ext_word) => {
-                match ext_word {
-                    ExtensionWord::Brief(reg_type, reg, size, scale, displacement) => {
-                        //todo: check if disp is out of range?
-                        let mut disp2 = displacement.resolve_value(labels, defines)? as i16;
-                        if let Value::Label(_) = displacement {
-                            disp2 -= location as i16 + 2;
-                        }
-        
-                        (
-                            0b111,
-                            0b011,
-                            vec![(reg_type.value() << 15) | ((*reg as u16) << 12) | ((*reg as u16) << 11) | ((*scale as u16) << 9) | ((disp2 as u16) & 0xFF)],
-                        )
-                    }
+            Self::PCIndex(ext_word) => (0b111, 0b011, ext_word.encode(labels, defines, location, true)?),
 
-                    ExtensionWord::Full(_, _, _, _, _, _, _, _) => todo!(),
+            Self::AbsoluteShort(value) | Self::Absolute(OpSize::W, value) => {
+                let addr = fits(value.resolve_value(labels, defines)?, 32)?;
+
+                if !absolute_short_fits(addr) {
+                    return Err(Log::ValueOutOfRange(addr as i64, -0x8000, 0x7FFF));
                 }
-            }
 
-            Self::AbsoluteShort(value) => {
-                let addr = value.resolve_value(labels, defines)?;
                 (0b111, 0b000, vec![addr as u16])
             }
 
-            Self::AbsoluteLong(value) => {
-                let addr = value.resolve_value(labels, defines)?;
+            Self::AbsoluteLong(value) | Self::Absolute(_, value) => {
+                let addr = fits(value.resolve_value(labels, defines)?, 32)?;
                 (0b111, 0b001, vec![(addr >> 16) as u16, addr as u16])
             }
 
+            Self::Immediate(size @ (OpSize::S | OpSize::D | OpSize::X | OpSize::P), value) => {
+                (0b111, 0b100, float::encode(value.resolve_float(labels, defines)?, *size)?)
+            }
+
             Self::Immediate(size, value) => {
-                //todo: warn if value exceeds size
-                let val = value.resolve_value(labels, defines)?;
+                let bits = match size {
+                    OpSize::B => 8,
+                    OpSize::W => 16,
+                    _ => 32,
+                };
+
+                let val = fits(value.resolve_value(labels, defines)?, bits)?;
 
                 (
                     0b111,
@@ -218,25 +536,27 @@ impl AddressingMode {
             }
 
             Self::BranchDisplacement(op_size, disp) => {
-                let size = match op_size {
-                    OpSize::B | OpSize::Unsized => 0, //let unsized through for dbcc
-                    OpSize::W => 1,
-                    _ => todo!(),
-                };
+                let disp2 = displacement(disp, labels, defines, location, true)?;
 
-                let mut disp2 = disp.resolve_value(labels, defines)? as i16;
-                if let Value::Label(_) = disp {
-                    disp2 -= location as i16 + 2;
+                if !branch_fits(*op_size, disp2) {
+                    return Err(Log::BranchOutOfRange);
                 }
 
-                (0, size, vec![disp2 as u16])
+                let ext = match op_size {
+                    OpSize::L => vec![(disp2 >> 16) as u16, disp2 as u16],
+                    _ => vec![disp2 as u16],
+                };
+
+                (0, 0, ext)
             }
 
             Self::CCR => (CCR_MASK >> 3, 0, vec![]),
             Self::SR => (SR_MASK >> 3, 0, vec![]),
             Self::USP => (USP_MASK >> 3, 0, vec![]),
 
-            Self::ControlReg(reg) => (0, 0, vec![]),
+            Self::ControlReg(_) | Self::RegisterPair(_, _) | Self::IndirectPair(_, _) | Self::Bitfield(_, _) | Self::Caches(_) |
+            Self::FloatRegister(_) | Self::FloatPair(_, _) | Self::FloatRegisterList(_) | Self::FloatControl(_) |
+            Self::MacPair(_, _, _) | Self::MacLoad(_, _, _) | Self::Accumulator(_) | Self::MacControl(_) => (0, 0, vec![]),
 
             Self::RegisterList(mask) => (MOVEM_MASK >> 3, 0, vec![*mask]),
             Self::DataQuick(imm) => (0b111, 0b100, vec![(imm.resolve_value(labels, defines)? & 0xFF) as u16]), //make dataquick look like immediate
@@ -247,6 +567,54 @@ impl AddressingMode {
     }
 }
 
+/// Resolves a displacement. Relative to the PC, values that are addresses are converted
+/// to an offset from the extension word at `location + 2`. A difference of labels, or any
+/// displacement from an address register, is used as is
+pub fn displacement(disp: &Value, labels: &HashMap<String, u32>, defines: &HashMap<String, Value>, location: u32, pc_relative: bool) -> Result<i64, Log> {
+    let value = disp.resolve_value(labels, defines)? as i64;
+
+    Ok(match pc_relative && disp.is_address() {
+        true  => value - (location as i64 + 2),
+        false => value,
+    })
+}
+
+/// Checks that a value fits in `bits` bits, as either a signed or an unsigned number
+pub fn fits(value: u64, bits: u32) -> Result<u64, Log> {
+    let (min, max) = (-(1i64 << (bits - 1)), (1i64 << bits) - 1);
+
+    match (min..=max).contains(&(value as i64)) {
+        true  => Ok(value),
+        false => Err(Log::ValueOutOfRange(value as i64, min, max)),
+    }
+}
+
+/// Checks that a value fits in `bits` bits as a signed number, used for displacements
+fn signed_fits(value: i64, bits: u32) -> Result<i64, Log> {
+    let (min, max) = (-(1i64 << (bits - 1)), (1i64 << (bits - 1)) - 1);
+
+    match (min..=max).contains(&value) {
+        true  => Ok(value),
+        false => Err(Log::ValueOutOfRange(value, min, max)),
+    }
+}
+
+/// Checks if an address can be used as an absolute short address, which is sign extended from 16 bits
+fn absolute_short_fits(addr: u64) -> bool {
+    let addr = addr as u32 as i32;
+    addr == addr as i16 as i32
+}
+
+/// Checks if a branch displacement can be encoded with the given size.
+/// 8-bit displacements of 0 and -1 are reserved to select 16 and 32-bit displacements
+pub fn branch_fits(size: OpSize, disp: i64) -> bool {
+    match size {
+        OpSize::B => (-128..=127).contains(&disp) && disp != 0 && disp != -1,
+        OpSize::W => (i16::MIN as i64..=i16::MAX as i64).contains(&disp),
+        _ => (i32::MIN as i64..=i32::MAX as i64).contains(&disp),
+    }
+}
+
 pub enum AddressingList {
     All,
     Alterable,
@@ -275,6 +643,35 @@ pub enum AddressingList {
     RegisterList,
     MovemSrc,
     MovemDst,
+
+    DataControl,
+    DataControlAlterable,
+    RegisterPair,
+    DataRegisterPair,
+    IndirectPair,
+    OffsetWidth,
+
+    AddressIndirect,
+    ControlAlterable,
+    Move16Operand,
+    FunctionCode,
+    Caches,
+
+    FloatRegister,
+    FloatSource,
+    FloatPair,
+    DynamicFloatList,
+    FloatControl,
+
+    DataIndirect,
+    Indirect,
+    AddressOrDisplacement,
+    DataRegisterImmediate,
+    RegisterImmediate,
+    MacPair,
+    Accumulator,
+    MacLoad,
+    MacControl,
 }
 
 impl AddressingList {
@@ -307,6 +704,35 @@ impl AddressingList {
             Self::RegisterList          => 0b0_000_010_000000000000,
             Self::MovemSrc              => 0b0_000_000_011001101100,
             Self::MovemDst              => 0b0_000_000_011001110100,
+
+            Self::DataControl           => 0b0_000_000_011111100101,
+            Self::DataControlAlterable  => 0b0_000_000_011001100101,
+            Self::RegisterPair          => 0b001_0_000_000_000000000000,
+            Self::DataRegisterPair      => 0b001_0_000_000_000000000001,
+            Self::IndirectPair          => 0b010_0_000_000_000000000000,
+            Self::OffsetWidth           => 0b100_0_000_000_000000000000,
+
+            Self::AddressIndirect       => 0b0_000_000_000000000100,
+            Self::ControlAlterable      => 0b0_000_000_011001100100,
+            Self::Move16Operand         => 0b0_000_000_010000001100,
+            Self::FunctionCode          => 0b1_000_100_000000000001,
+            Self::Caches                => 0b1000_0_000_000_000000000000,
+
+            Self::FloatRegister         => 0b1_0000_0_000_000_000000000000,
+            Self::FloatSource           => 0b1_0000_0_000_000_111111111101,
+            Self::FloatPair             => 0b10_0000_0_000_000_000000000000,
+            Self::DynamicFloatList      => 0b101_0000_0_000_000_000000000001,
+            Self::FloatControl          => 0b1000_0000_0_000_000_000000000000,
+
+            Self::DataIndirect          => 0b0_000_000_000000111101,
+            Self::Indirect              => 0b0_000_000_000000111100,
+            Self::AddressOrDisplacement => 0b0_000_000_000000100100,
+            Self::DataRegisterImmediate => 0b0_000_000_100000000001,
+            Self::RegisterImmediate     => 0b0_000_000_100000000011,
+            Self::MacPair               => 0b1_0000_0000_0_000_000_000000000000,
+            Self::Accumulator           => 0b10_0000_0000_0_000_000_000000000000,
+            Self::MacLoad               => 0b100_0000_0000_0_000_000_000000000000,
+            Self::MacControl            => 0b1000_0000_0000_0_000_000_000000000000,
         };
 
         mode.mask_bit() & list_mask != 0
@@ -316,22 +742,25 @@ impl AddressingList {
 pub fn determine_addressing_mode(token: &str, opcode: &OpType, size: OpSize, last_label: &str, extended_addressing: bool) -> Result<AddressingMode, Log> {
     use AddressingMode::*;
 
-    if token.len() == 2 {
-        if let Ok(reg) = parse_reg(&token[1..=1]) {
-            if token.to_uppercase().starts_with('D') {
-                return Ok( match opcode {
-                    OpType::Movem => RegisterList(1 << reg),
-                    _ => DataRegister(reg),
-                })
-            } else if token.to_uppercase().starts_with('A') {
-                return Ok( match opcode {
-                    OpType::Movem => RegisterList(1 << (reg + 8)),
-                    _ => AddressRegister(reg),
-                })
-            }
+    if let Some((reg_type, reg)) = register(token)? {
+        return Ok( match (opcode, reg_type) {
+            (OpType::Movem, RegType::Dn) => RegisterList(1 << reg),
+            (OpType::Movem, RegType::An) => RegisterList(1 << (reg + 8)),
+            (_, RegType::Dn) => DataRegister(reg),
+            (_, RegType::An) => AddressRegister(reg),
+        })
+    }
+
+    if opcode.is_fpu() {
+        if let Some(mode) = float_operand(token)? {
+            return Ok(mode);
         }
     }
 
+    if let Some(pair) = register_pair(token)? {
+        return Ok(pair);
+    }
+
     match token.to_uppercase().as_str() {
         "CCR" => return Ok(CCR),
         "SR"  => return Ok(SR),
@@ -340,213 +769,623 @@ pub fn determine_addressing_mode(token: &str, opcode: &OpType, size: OpSize, las
         "SFC" => return Ok(ControlReg(ControlRegister::Sfc)),
         "DFC" => return Ok(ControlReg(ControlRegister::Dfc)),
         "VBR" => return Ok(ControlReg(ControlRegister::Vbr)),
+
+        name if matches!(opcode, OpType::Movec | OpType::PMove(_)) => {
+            if let Some(cr) = ControlRegister::parse(name) {
+                return Ok(ControlReg(cr));
+            }
+        }
+
+        "NC" if matches!(opcode, OpType::Cache(_)) => return Ok(Caches(0b00)),
+        "DC" if matches!(opcode, OpType::Cache(_)) => return Ok(Caches(0b01)),
+        "IC" if matches!(opcode, OpType::Cache(_)) => return Ok(Caches(0b10)),
+        "BC" if matches!(opcode, OpType::Cache(_)) => return Ok(Caches(0b11)),
         _ => (),
     }
 
     if let Some(imm) = token.strip_prefix('#') {
-        let val = Value::new(imm, last_label);
+        let val = Value::new(imm, last_label)?;
 
         Ok( match opcode {
-            OpType::MoveQ | OpType::Rotation(_, _) | OpType::AddSubQ(_) |
-            OpType::Trap | OpType::Bkpt => DataQuick(val),
-            OpType::Rtd => Immediate(OpSize::W, val),
+            OpType::MoveQ | OpType::Rotation(_, _) | OpType::AddSubQ(_) | OpType::Mov3q |
+            OpType::Trap | OpType::Bkpt |
+            OpType::PFlush030(_) | OpType::PLoad(_) | OpType::PTest030(_) => DataQuick(val),
+            OpType::Rtd | OpType::Stop | OpType::Pack(_) | OpType::Lpstop => Immediate(OpSize::W, val),
+            OpType::TrapCc(_) if size == OpSize::Unsized => Immediate(OpSize::W, val),
+            OpType::Callm => Immediate(OpSize::B, val),
+            OpType::FMovecr => DataQuick(val),
+            OpType::FTrapcc(_) if size == OpSize::Unsized => Immediate(OpSize::W, val),
+            _ if opcode.is_fpu() && size == OpSize::Unsized => Immediate(OpSize::X, val), // extended precision by default
             _ => Immediate(size, val),
         })
-    } else if let Some(predec) = token.strip_prefix("-(A") {
-        if let Some(predec) = predec.strip_suffix(')') {
-            Ok(AddressPredecrement(parse_reg(predec)?))
-        } else {
-            todo!("error")
+    } else if let Some(predec) = token.strip_prefix("-(") {
+        match predec.strip_suffix(')') {
+            Some(reg) => Ok(AddressPredecrement(address_register(reg, token)?)),
+            None => Err(Log::UnclosedParenthesis(token.to_string())),
         }
     } else if let Some(postinc) = token.strip_suffix(")+") {
-        if let Some(postinc) = postinc.strip_prefix("(A") {
-            Ok(AddressPostincrement(parse_reg(postinc)?))
-        } else {
-            todo!("error")
-        }
-    } else if let Some(paren_token) = token.strip_prefix('(') {
-        if let Some(paren_token) = paren_token.strip_suffix(')') {
-            let commas: Vec<_> = paren_token.match_indices(',').collect();
-
-            match commas.len() {
-                0 => {
-                    if paren_token.len() == 2 && paren_token.to_uppercase().starts_with('A') {
-                        Ok(Address(parse_reg(&paren_token[1..=1])?))
-                    } else {
-                        todo!("error")
-                    }
-                }
+        match postinc.strip_prefix('(') {
+            Some(reg) => Ok(AddressPostincrement(address_register(reg, token)?)),
+            None => Err(Log::UnopenedParenthesis(token.to_string())),
+        }
+    } else if let Some((outer_disp, paren_token)) = indirect(token)? {
+        let mut parts: Vec<&str> = paren_token.split(',').map(|p| p.trim()).collect();
 
-                1 => {
-                    let disp = Value::new(paren_token[..commas[0].0].trim(), last_label);
-                    let second = paren_token[commas[0].0 + 1..paren_token.len()].trim();
+        if !outer_disp.is_empty() {
+            parts.insert(0, outer_disp);
+        }
 
-                    if second == "PC" {
-                        Ok(PCDisplacement(disp))
-                    } else if second.len() == 2 && second.starts_with('A') {
-                        Ok(AddressDisplacement(disp, parse_reg(&second[1..=1])?))
-                    } else {
-                        Err(Log::InvalidAddressingMode)
-                    }
+        if paren_token.trim_start().starts_with('[') || needs_full_format(&parts) {
+            return match extended_addressing {
+                true  => full_format(outer_disp, paren_token, token, last_label),
+                false => Err(Log::CpuTypeModeNotValid),
+            };
+        }
+
+        let indexed = |disp: Value, base: &str, index: &str| -> Result<AddressingMode, Log> {
+            let (reg_type, reg_num, reg_size, scale) = index_register(index, token, extended_addressing)?;
+            let ext_word = ExtensionWord::Brief(reg_type, reg_num, reg_size, scale, disp);
+
+            if base.eq_ignore_ascii_case("PC") {
+                Ok(PCIndex(ext_word))
+            } else {
+                match register(base)? {
+                    Some((RegType::An, reg)) => Ok(AddressIndex(ext_word, reg)),
+                    _ => Err(Log::InvalidBaseRegister(token.to_string())),
                 }
+            }
+        };
+
+        match parts[..] {
+            [reg] => Ok(Address(address_register(reg, token)?)),
 
-                2 => {
-                    let disp = Value::new(paren_token[..commas[0].0].trim(), last_label);
-
-                    let third = paren_token[commas[1].0 + 1..paren_token.len()].trim();
-
-                    // more like, if scale detected, check there
-                    // if !extended_addressing && third.len() != 4 {
-                    //     todo!("error")
-                    // }
-
-                    let reg_type = if third.starts_with('D') {
-                        RegType::Dn
-                    } else if third.starts_with('A') {
-                        RegType::An
-                    } else {
-                        todo!("error")
-                    };
-
-                    let reg_num = parse_reg(&third[1..=1])?;
-
-                    //todo: refactor once all new addressing is in
-
-                    if !extended_addressing {
-                        let reg_size = if third.to_lowercase().ends_with(".w") {
-                            false
-                        } else if third.to_lowercase().ends_with(".l") {
-                            true
-                        } else {
-                            return Err(Log::IndexRegisterInvalidSize)
-                        };
-    
-                        let second = paren_token[commas[0].0 + 1..commas[1].0].trim();
-    
-                        if second == "PC" {
-                            //todo: scale hardcoded to 0!
-                            Ok(PCIndex(ExtensionWord::Brief(reg_type, reg_num, reg_size, 0, disp)))
-                        } else if second.len() == 2 && second.starts_with('A') {
-                            //todo: scale hardcoded to 0!
-                            Ok(AddressIndex(ExtensionWord::Brief(reg_type, reg_num, reg_size, 0, disp), parse_reg(&second[1..=1])?))
-                        } else {
-                            todo!("error")
-                        }
-                    } else {
-                        let reg_size = if third[2..=3].to_lowercase() == ".w" {
-                            false
-                        } else if third[2..=3].to_lowercase() == ".l" {
-                            true
-                        } else {
-                            return Err(Log::IndexRegisterInvalidSize)
-                        };
-
-                        let mut scale = 1;
-                        match third.split_once('*') {
-                            Some((ind_reg, scale_str)) => { //todo: use ind_reg for something or ignore?
-                                match scale_str.trim().parse::<u8>() {
-                                    Ok(s) => {
-                                        match s {
-                                            1 => scale = 0,
-                                            2 => scale = 1,
-                                            4 => scale = 2,
-                                            8 => scale = 3,
-                                            _ => todo!("invalid scale"),
-                                        }
-                                    }
-
-                                    Err(_) => todo!("invalid scale"),
-                                }
-                            }
-
-                            None => (),
-                        }
-
-                        let second = paren_token[commas[0].0 + 1..commas[1].0].trim();
-    
-                        if second == "PC" {
-                            Ok(PCIndex(ExtensionWord::Brief(reg_type, reg_num, reg_size, scale, disp)))
-                        } else if second.len() == 2 && second.starts_with('A') {
-                            Ok(AddressIndex(ExtensionWord::Brief(reg_type, reg_num, reg_size, scale, disp), parse_reg(&second[1..=1])?))
-                        } else {
-                            todo!("error")
-                        }
+            // (An, Xn) is short for 0(An, Xn)
+            [base, index] if base.eq_ignore_ascii_case("PC") || matches!(register(base), Ok(Some((RegType::An, _)))) => {
+                indexed(Value::Number(0), base, index)
+            }
+
+            [disp, base] => {
+                let disp = Value::new(disp, last_label)?;
+
+                if base.eq_ignore_ascii_case("PC") {
+                    Ok(PCDisplacement(disp))
+                } else {
+                    match register(base)? {
+                        Some((RegType::An, reg)) => Ok(AddressDisplacement(disp, reg)),
+                        _ => Err(Log::InvalidBaseRegister(token.to_string())),
                     }
                 }
-
-                _ => todo!("error")
             }
-        } else {
-            todo!("error")
+
+            [disp, base, index] => indexed(Value::new(disp, last_label)?, base, index),
+
+            _ => Err(Log::TooManyIndexParts(token.to_string())),
         }
-    }  else if let Some(abs_w) = token.strip_suffix(".w") {
-        Ok(AbsoluteShort(Value::new(abs_w, last_label)))
+    } else if let Some(abs_w) = token.strip_suffix(".w") {
+        Ok(AbsoluteShort(Value::new(abs_w, last_label)?))
     } else if let Some(abs_l) = token.strip_suffix(".l") {
-        Ok(AbsoluteLong(Value::new(abs_l, last_label)))
+        Ok(AbsoluteLong(Value::new(abs_l, last_label)?))
     } else {
         match opcode {
             OpType::Movem => Ok(RegisterList(movem(token)?)),
 
-            OpType::Branch(_) | OpType::Dbcc(_) => Ok(BranchDisplacement(size, Value::new(token, last_label))),
+            OpType::Branch(_) => {
+                let size = match size {
+                    OpSize::Unsized => OpSize::B, //start out small, grown if needed once labels are known
+                    _ => size,
+                };
 
-            _ => {
-                Ok(AbsoluteLong(Value::new(token, last_label))) //todo: pick short/long based on value size
+                Ok(BranchDisplacement(size, Value::new(token, last_label)?))
             }
+
+            OpType::Dbcc(_) | OpType::FDbcc(_) => Ok(BranchDisplacement(OpSize::W, Value::new(token, last_label)?)),
+
+            OpType::FBranch(_) => {
+                let size = match size {
+                    OpSize::Unsized => OpSize::W, // there is no byte form
+                    _ => size,
+                };
+
+                Ok(BranchDisplacement(size, Value::new(token, last_label)?))
+            }
+            OpType::Move16 => Ok(AbsoluteLong(Value::new(token, last_label)?)),
+
+            _ => Ok(Absolute(OpSize::W, Value::new(token, last_label)?)), //start out short, grown if needed once labels are known
         }
     }
 }
 
-fn movem(token: &str) -> Result<u16, Log> {
-    let mut mask = 0;
-    let v: Vec<&str> = token.split('/').collect();
+/// Parses `Dh:Dl` and `(Rn):(Rn)` register pairs
+fn register_pair(token: &str) -> Result<Option<AddressingMode>, Log> {
+    let Some((a, b)) = token.split_once(':') else {
+        return Ok(None);
+    };
 
-    for &section in v.iter() {
-        if section.contains('-') {
-            let range = {
-                let (a, b) = section.split_once('-').unwrap();
+    let (a, b) = (a.trim(), b.trim());
 
-                let base = if a.starts_with('D') && b.starts_with('D') {
-                    0
-                } else if a.starts_with('A') && b.starts_with('A') {
-                    8
-                } else {
-                    todo!()
-                };
+    fn indirect(reg: &str) -> Option<&str> {
+        reg.strip_prefix('(')?.strip_suffix(')').map(str::trim)
+    }
 
-                let (x, y) = (parse_reg(&a[1..])? + base, parse_reg(&b[1..])? + base);
+    if let (Some(a), Some(b)) = (indirect(a), indirect(b)) {
+        return match (register(a)?, register(b)?) {
+            (Some(a), Some(b)) => Ok(Some(AddressingMode::IndirectPair(general_register(a), general_register(b)))),
+            _ => Err(Log::InvalidAddressingMode),
+        };
+    }
 
-                match x <= y {
-                    true  => x..=y,
-                    false => y..=x,
-                }
+    match (register(a)?, register(b)?) {
+        (Some((RegType::Dn, a)), Some((RegType::Dn, b))) => Ok(Some(AddressingMode::RegisterPair(a, b))),
+        (Some(_), Some(_)) => Err(Log::InvalidAddressingMode),
+        _ => Ok(None),
+    }
+}
+
+/// Register number as used in extension words, 0-7 for data registers and 8-15 for address registers
+fn general_register((reg_type, reg): (RegType, u8)) -> u8 {
+    reg + (reg_type.value() as u8) * 8
+}
+
+enum FloatReg {
+    Data(u8),
+    Control(u8),
+}
+
+/// Parses FP0-FP7, FPCR, FPSR and FPIAR. Returns None if the token isn't an FPU register
+fn float_register(token: &str) -> Result<Option<FloatReg>, Log> {
+    let name = token.trim().to_uppercase();
+
+    match name.as_str() {
+        "FPCR"  => return Ok(Some(FloatReg::Control(0b100))),
+        "FPSR"  => return Ok(Some(FloatReg::Control(0b010))),
+        "FPIAR" => return Ok(Some(FloatReg::Control(0b001))),
+        _ => (),
+    }
+
+    match name.strip_prefix("FP") {
+        Some(number) if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) => match number.parse::<u8>() {
+            Ok(reg) if reg < 8 => Ok(Some(FloatReg::Data(reg))),
+            _ => Err(Log::InvalidRegister(token.trim().to_string())),
+        },
+
+        _ => Ok(None),
+    }
+}
+
+/// Parses FPU registers, `FPc:FPs` pairs and register lists like `FP0-FP3/FP7` or `FPCR/FPSR`.
+/// Returns None if the token isn't made of FPU registers
+fn float_operand(token: &str) -> Result<Option<AddressingMode>, Log> {
+    if let Some((a, b)) = token.split_once(':') {
+        return match (float_register(a)?, float_register(b)?) {
+            (Some(FloatReg::Data(c)), Some(FloatReg::Data(s))) => Ok(Some(AddressingMode::FloatPair(c, s))),
+            (None, None) => Ok(None),
+            _ => Err(Log::InvalidAddressingMode),
+        };
+    }
+
+    let (mut data, mut control, mut unknown) = (0u8, 0u8, false);
+
+    for section in token.split('/') {
+        let range = match section.split_once('-') {
+            Some((a, b)) => (float_register(a)?, float_register(b)?),
+            None => (float_register(section)?, None),
+        };
+
+        match range {
+            (Some(FloatReg::Data(x)), Some(FloatReg::Data(y))) => (x.min(y) ..= x.max(y)).for_each(|reg| data |= 1 << reg),
+            (Some(FloatReg::Data(reg)), None) if !section.contains('-') => data |= 1 << reg,
+            (Some(FloatReg::Control(reg)), None) if !section.contains('-') => control |= reg,
+            (None, None) => unknown = true,
+            _ => return Err(Log::InvalidRegisterRange(section.trim().to_string())),
+        }
+    }
+
+    match (data, control, unknown) {
+        (0, 0, _) => Ok(None),
+        (_, _, true) => Err(Log::InvalidRegisterList(token.to_string())),
+        (data, 0, _) if !token.contains(['/', '-']) => Ok(Some(AddressingMode::FloatRegister(data.trailing_zeros() as u8))),
+        (data, 0, _) => Ok(Some(AddressingMode::FloatRegisterList(data))),
+        (0, control, _) => Ok(Some(AddressingMode::FloatControl(control))),
+        _ => Err(Log::InvalidRegisterList(token.to_string())),
+    }
+}
+
+/// Parses the EMAC registers ACC0-ACC3 (ACC is ACC0), MACSR, MASK, ACCEXT01 and ACCEXT23. Returns None for anything else
+pub fn mac_register(token: &str) -> Option<AddressingMode> {
+    Some(match token.trim().to_uppercase().as_str() {
+        "ACC" | "ACC0" => AddressingMode::Accumulator(0),
+        "ACC1" => AddressingMode::Accumulator(1),
+        "ACC2" => AddressingMode::Accumulator(2),
+        "ACC3" => AddressingMode::Accumulator(3),
+        "MACSR"    => AddressingMode::MacControl(0b100),
+        "ACCEXT01" => AddressingMode::MacControl(0b101),
+        "MASK"     => AddressingMode::MacControl(0b110),
+        "ACCEXT23" => AddressingMode::MacControl(0b111),
+        _ => return None,
+    })
+}
+
+/// Operands of mac and msac, `Ry, Rx[<< | >>][, ACCx]`, or `Ry, Rx[<< | >>], <ea>[&], Rw[, ACCx]` to also load Rw.
+/// Ry and Rx can take `.u` or `.l` for the upper or lower word, and the accumulator defaults to ACC0.
+/// They don't fit in three operands, so Ry and Rx are packed into the first and Rw and the accumulator into the last
+pub fn mac_operands(operands: &[String], opcode: &OpType, size: OpSize, last_label: &str, extended_addressing: bool) -> Result<[AddressingMode; 3], Log> {
+    // bit 3 is set for address registers, like the ea register field
+    let mac_reg = |token: &str| -> Result<u8, Log> {
+        match register(token)? {
+            Some((reg_type, reg)) => Ok((((reg_type == RegType::An) as u8) << 3) | reg),
+            None => Err(Log::InvalidRegister(token.to_string())),
+        }
+    };
+
+    let half_reg = |token: &str| -> Result<u8, Log> {
+        match token.rsplit_once('.') {
+            Some((reg, half)) if half.eq_ignore_ascii_case("u") => Ok((1 << 4) | mac_reg(reg)?),
+            Some((reg, half)) if half.eq_ignore_ascii_case("l") => mac_reg(reg),
+            Some(_) => Err(Log::InvalidRegister(token.to_string())),
+            None => mac_reg(token),
+        }
+    };
+
+    let accumulator = |token: Option<&String>| -> Result<u8, Log> {
+        match token.map(|token| mac_register(token)) {
+            None => Ok(0),
+            Some(Some(AddressingMode::Accumulator(acc))) => Ok(acc),
+            Some(_) => Err(Log::InvalidAddressingMode),
+        }
+    };
+
+    let [ry, rx, rest @ ..] = operands else {
+        return Err(Log::InvalidAddressingMode);
+    };
+
+    // the product can be shifted left or right by one
+    let (rx, scale) = if let Some(rx) = rx.strip_suffix("<<1").or(rx.strip_suffix("<<")) {
+        (rx, 0b01)
+    } else if let Some(rx) = rx.strip_suffix(">>1").or(rx.strip_suffix(">>")) {
+        (rx, 0b11)
+    } else {
+        (rx.as_str(), 0b00)
+    };
+
+    let pair = AddressingMode::MacPair(half_reg(ry)?, half_reg(rx.trim_end())?, scale);
+
+    match rest {
+        [] | [_] => Ok([pair, AddressingMode::Empty, AddressingMode::Accumulator(accumulator(rest.first())?)]),
+
+        [ea, rw, acc @ ..] => {
+            if let Some(extra) = acc.get(1) {
+                return Err(Log::ExtraOperand(extra.to_string()));
+            }
+
+            let (ea, mask) = match ea.strip_suffix('&') {
+                Some(ea) => (ea.trim_end(), true),
+                None => (ea.as_str(), false),
+            };
+
+            let ea = determine_addressing_mode(ea, opcode, size, last_label, extended_addressing)?;
+            Ok([pair, ea, AddressingMode::MacLoad(mac_reg(rw)?, accumulator(acc.first())?, mask)])
+        }
+    }
+}
+
+/// Splits the `{offset:width}` off the end of a bitfield operand
+pub fn bitfield<'a>(operand: &'a str, last_label: &str) -> Result<(&'a str, AddressingMode), Log> {
+    let Some((ea, field)) = operand.strip_suffix('}').and_then(|operand| operand.rsplit_once('{')) else {
+        return Err(Log::MissingBitfield(operand.to_string()));
+    };
+
+    let Some((offset, width)) = field.split_once(':') else {
+        return Err(Log::MissingBitfield(operand.to_string()));
+    };
+
+    let part = |text: &str| -> Result<BitfieldPart, Log> {
+        match register(text.trim())? {
+            Some((RegType::Dn, reg)) => Ok(BitfieldPart::Register(reg)),
+            Some(_) => Err(Log::InvalidAddressingMode),
+            None => Ok(BitfieldPart::Value(Value::new(text, last_label)?)),
+        }
+    };
+
+    Ok((ea.trim(), AddressingMode::Bitfield(part(offset)?, part(width)?)))
+}
+
+/// Base register of a full format operand
+enum Base {
+    An(u8),
+    PC,
+    Suppressed(Option<u8>), // ZAn, or no base register at all
+    SuppressedPC,           // ZPC
+}
+
+/// Parses the 68020 addressing modes that need a full extension word: memory indirect `([bd, An, Xn], od)` and `([bd, An], Xn, od)`,
+/// and `(bd, An, Xn)` with a sized or 32-bit displacement, or an omitted or suppressed (ZAn, ZPC, ZDn) base or index register.
+/// All parts are optional
+fn full_format(outer_disp: &str, inner: &str, token: &str, last_label: &str) -> Result<AddressingMode, Log> {
+    let split = |text: &str| -> Vec<String> {
+        match text.trim().is_empty() {
+            true  => vec![],
+            false => text.split(',').map(|part| part.trim().to_string()).collect(),
+        }
+    };
+
+    let (mut inside, outside, memory_indirect) = match inner.trim_start().strip_prefix('[') {
+        Some(rest) => {
+            let Some((inside, outside)) = rest.split_once(']') else {
+                return Err(Log::MismatchedParentheses(token.to_string()));
+            };
+
+            let outside = match outside.trim() {
+                "" => vec![],
+                text => match text.strip_prefix(',') {
+                    Some(text) => split(text),
+                    None => return Err(Log::InvalidAddressingMode),
+                },
+            };
+
+            if !outer_disp.is_empty() {
+                return Err(Log::InvalidAddressingMode);
+            }
+
+            (split(inside), outside, true)
+        }
+
+        None => (split(inner), vec![], false),
+    };
+
+    if !outer_disp.is_empty() {
+        inside.insert(0, outer_disp.to_string());
+    }
+
+    let (mut bd, mut base, mut index) = (None, None, None);
+
+    // in order: base displacement, base register, index register
+    for part in &inside {
+        let as_base = match base.is_none() && index.is_none() {
+            true  => base_register(part)?,
+            false => None,
+        };
+
+        let as_index = match as_base.is_none() && index.is_none() {
+            true  => full_index_register(part, token)?,
+            false => None,
+        };
+
+        match (as_base, as_index) {
+            (Some(reg), _) => base = Some(reg),
+            (None, Some(reg)) => index = Some(reg),
+            (None, None) if bd.is_none() && base.is_none() && index.is_none() => bd = Some(part.as_str()),
+            (None, None) => return Err(Log::TooManyIndexParts(token.to_string())),
+        }
+    }
+
+    let pre_indexed = index.is_some();
+    let mut od = None;
+
+    // in order: index register if it wasn't inside the brackets, outer displacement
+    for part in &outside {
+        let as_index = match index.is_none() && od.is_none() {
+            true  => full_index_register(part, token)?,
+            false => None,
+        };
+
+        match as_index {
+            Some(reg) => index = Some(reg),
+            None if od.is_none() => od = Some(part.as_str()),
+            None => return Err(Log::TooManyIndexParts(token.to_string())),
+        }
+    }
+
+    let displacement = |disp: Option<&str>| match disp {
+        Some(disp) => Displacement::new(disp, last_label),
+        None => Ok(Displacement::Null),
+    };
+
+    // without an index register, pre and post indexing are the same
+    let post_indexed = !pre_indexed && matches!(index, Some(Some(_)));
+
+    let indirect = match (memory_indirect, post_indexed) {
+        (false, _) => MemoryIndirect::None,
+        (true, false) => MemoryIndirect::PreIndexed(displacement(od)?),
+        (true, true) => MemoryIndirect::PostIndexed(displacement(od)?),
+    };
+
+    let base = base.unwrap_or(Base::Suppressed(None));
+    let base_suppressed = matches!(base, Base::Suppressed(_) | Base::SuppressedPC);
+    let ext_word = ExtensionWord::Full(index.flatten(), base_suppressed, displacement(bd)?, indirect);
+
+    Ok(match base {
+        Base::An(reg) | Base::Suppressed(Some(reg)) => AddressingMode::AddressIndex(ext_word, reg),
+        Base::Suppressed(None) => AddressingMode::AddressIndex(ext_word, 0),
+        Base::PC | Base::SuppressedPC => AddressingMode::PCIndex(ext_word),
+    })
+}
+
+/// Checks if an operand like `(bd, An, Xn)` needs a full extension word regardless of the displacement's value,
+/// which is the case for suppressed registers, sized displacements and `(bd, Xn)` or `(Xn)` without a base register
+fn needs_full_format(parts: &[&str]) -> bool {
+    let suppressed = parts.iter().any(|part| suppressed_register(part).is_some());
+    let sized_disp = parts.first().is_some_and(|disp| disp.ends_with(".w") || disp.ends_with(".l")) && parts.len() > 1;
+    let no_base = match parts {
+        [index] => matches!(full_index_register(index, ""), Ok(Some(_))),
+        [disp, index] => !matches!(base_register(disp), Ok(Some(_))) && matches!(full_index_register(index, ""), Ok(Some(_))),
+        _ => false,
+    };
+
+    suppressed || sized_disp || no_base
+}
+
+/// The register after the Z of a suppressed register like ZA0, ZD1.l or ZPC
+fn suppressed_register(part: &str) -> Option<&str> {
+    let reg = part.strip_prefix(['Z', 'z'])?;
+    let name = reg.split(['.', '*']).next().unwrap_or(reg).trim();
+
+    match name.eq_ignore_ascii_case("PC") || matches!(register(name), Ok(Some(_))) {
+        true  => Some(reg),
+        false => None,
+    }
+}
+
+fn base_register(part: &str) -> Result<Option<Base>, Log> {
+    if part.eq_ignore_ascii_case("PC") {
+        return Ok(Some(Base::PC));
+    }
+
+    if let Some(reg) = suppressed_register(part) {
+        return Ok(match register(reg) {
+            _ if reg.eq_ignore_ascii_case("PC") => Some(Base::SuppressedPC),
+            Ok(Some((RegType::An, reg))) => Some(Base::Suppressed(Some(reg))),
+            _ => None,
+        });
+    }
+
+    match register(part)? {
+        Some((RegType::An, reg)) => Ok(Some(Base::An(reg))),
+        _ => Ok(None),
+    }
+}
+
+/// Parses an index register for a full extension word, Some(None) if it's suppressed
+fn full_index_register(part: &str, operand: &str) -> Result<Option<Option<Index>>, Log> {
+    if suppressed_register(part).is_some() {
+        return Ok(Some(None));
+    }
+
+    let name = part.split(['.', '*']).next().unwrap_or(part).trim();
+
+    match register(name)? {
+        Some(_) => Ok(Some(Some(index_register(part, operand, true)?))),
+        None => Ok(None),
+    }
+}
+
+/// Parses an index register with its size and optional scale, like "D0.w" or "A1.l*4"
+fn index_register(index: &str, operand: &str, extended_addressing: bool) -> Result<(RegType, u8, bool, u8), Log> {
+    let (reg, scale) = match index.split_once('*') {
+        Some((reg, scale)) => (reg.trim(), Some(scale.trim())),
+        None => (index, None),
+    };
+
+    let scale = match scale {
+        None | Some("1") => 0,
+        Some(_) if !extended_addressing => return Err(Log::CpuTypeModeNotValid),
+        Some("2") => 1,
+        Some("4") => 2,
+        Some("8") => 3,
+        Some(_) => return Err(Log::InvalidScale(operand.to_string())),
+    };
+
+    let (reg, reg_size) = match reg.rsplit_once('.') {
+        Some((reg, size)) if size.eq_ignore_ascii_case("w") => (reg, false),
+        Some((reg, size)) if size.eq_ignore_ascii_case("l") => (reg, true),
+        _ => return Err(Log::IndexRegisterInvalidSize(operand.to_string())),
+    };
+
+    match register(reg)? {
+        Some((reg_type, reg_num)) => Ok((reg_type, reg_num, reg_size, scale)),
+        None => Err(Log::InvalidIndexRegister(operand.to_string())),
+    }
+}
+
+/// Splits `disp(An, Xn)` and `(disp, An, Xn)` into the part before the parentheses and the part inside of them.
+/// Returns None if the parentheses are part of an expression instead
+fn indirect(token: &str) -> Result<Option<(&str, &str)>, Log> {
+    let Some(without_close) = token.strip_suffix(')') else {
+        return Ok(None);
+    };
+
+    let mut depth = 0;
+
+    let open = without_close.char_indices().rev().find(|&(_, c)| {
+        match c {
+            ')' => depth += 1,
+            '(' if depth == 0 => return true,
+            '(' => depth -= 1,
+            _ => (),
+        }
+
+        false
+    });
+
+    let Some((open, _)) = open else {
+        return Err(Log::UnopenedParenthesis(token.to_string()));
+    };
+
+    let (disp, inner) = (token[.. open].trim(), &without_close[open + 1 ..]);
+    let first = inner.split(',').next().unwrap_or("").trim();
+    let name = first.split(['.', '*']).next().unwrap_or(first).trim();
+
+    // invalid registers count as registers so they are reported as such, and so do suppressed and index registers
+    let is_register = first.eq_ignore_ascii_case("PC") || suppressed_register(first).is_some() || !matches!(register(name), Ok(None));
+
+    match inner.contains(',') || is_register || inner.trim_start().starts_with('[') {
+        true  => Ok(Some((disp, inner))),
+        false => Ok(None),
+    }
+}
+
+fn address_register(token: &str, operand: &str) -> Result<u8, Log> {
+    match register(token.trim())? {
+        Some((RegType::An, reg)) => Ok(reg),
+        _ => Err(Log::ExpectedAddressRegister(operand.to_string())),
+    }
+}
+
+fn movem(token: &str) -> Result<u16, Log> {
+    let mut mask = 0;
+
+    let list_register = |name: &str| -> Result<(RegType, u8), Log> {
+        match register(name.trim())? {
+            Some(reg) => Ok(reg),
+            None => Err(Log::InvalidRegisterList(token.to_string())),
+        }
+    };
+
+    for section in token.split('/') {
+        if let Some((a, b)) = section.split_once('-') {
+            let (range, base) = match (list_register(a)?, list_register(b)?) {
+                ((RegType::Dn, x), (RegType::Dn, y)) => ((x, y), 0),
+                ((RegType::An, x), (RegType::An, y)) => ((x, y), 8),
+                _ => return Err(Log::InvalidRegisterRange(section.trim().to_string())),
+            };
+
+            let range = match range.0 <= range.1 {
+                true  => range.0..=range.1,
+                false => range.1..=range.0,
             };
 
             for x in range {
-                mask |= 1 << x;
+                mask |= 1 << (x + base);
             }
         } else {
-            let base = if section.starts_with('D') {
-                0
-            } else if section.starts_with('A') {
-                8
-            } else {
-                todo!()
+            mask |= match list_register(section)? {
+                (RegType::Dn, reg) => 1 << reg,
+                (RegType::An, reg) => 1 << (reg + 8),
             };
-
-            mask |= 1 << (parse_reg(&section[1..])? + base);
         }
     }
 
     Ok(mask)
 }
 
-fn parse_reg(token: &str) -> Result<u8, Log> {
-    match token.parse::<u32>() {
-        Ok(reg) => match reg < 8 {
-            true  => Ok(reg as u8),
-            false => Err(Log::InvalidRegister),
-        }
+/// Parses a data or address register like "D3" or "a7".
+/// Returns None if the token doesn't look like a register at all
+fn register(token: &str) -> Result<Option<(RegType, u8)>, Log> {
+    let mut chars = token.chars();
+
+    let reg_type = match chars.next().map(|c| c.to_ascii_uppercase()) {
+        Some('D') => RegType::Dn,
+        Some('A') => RegType::An,
+        _ => return Ok(None),
+    };
+
+    let number = chars.as_str();
+
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(None);
+    }
 
-        Err(_) => Err(Log::InvalidRegister),
+    match number.parse::<u8>() {
+        Ok(reg) if reg < 8 => Ok(Some((reg_type, reg))),
+        _ => Err(Log::InvalidRegister(token.to_string())),
     }
 }
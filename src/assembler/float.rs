@@ -0,0 +1,82 @@
+use super::OpSize;
+use crate::logging::Log;
+
+/// Words of a floating point value in the FPU formats: single, double, extended and packed decimal
+pub fn encode(value: f64, size: OpSize) -> Result<Vec<u16>, Log> {
+    // values are calculated as doubles, so that is also the limit for extended and packed
+    let (rounded, max) = match size {
+        OpSize::S => (value as f32 as f64, f32::MAX as f64),
+        _ => (value, f64::MAX),
+    };
+
+    if rounded.is_infinite() {
+        return Err(Log::FloatOutOfRange(size.suffixes()[0], max));
+    }
+
+    Ok(match size {
+        OpSize::S => words(&(value as f32).to_bits().to_be_bytes()),
+        OpSize::D => words(&value.to_bits().to_be_bytes()),
+        OpSize::X => extended(value),
+        OpSize::P => packed(value),
+        _ => unreachable!(),
+    })
+}
+
+fn words(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect()
+}
+
+/// Sign and 15-bit exponent, a word of padding and a 64-bit mantissa with an explicit integer bit
+fn extended(value: f64) -> Vec<u16> {
+    let bits = value.to_bits();
+    let sign = ((bits >> 63) as u16) << 15;
+    let exponent = ((bits >> 52) & 0x7FF) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+
+    let (exponent, mantissa) = match (exponent, fraction) {
+        (0, 0) => (0, 0),
+        (0x7FF, 0) => (0x7FFF, 0),
+        (0x7FF, _) => (0x7FFF, (1 << 63) | (fraction << 11)),
+
+        // subnormal doubles are normal in extended precision
+        (0, _) => {
+            let shift = fraction.leading_zeros() as i32;
+            (63 - shift - 1074 + 16383, fraction << shift)
+        }
+
+        _ => (exponent - 1023 + 16383, (1 << 63) | (fraction << 11)),
+    };
+
+    let mut vec = vec![sign | exponent as u16, 0];
+    vec.extend(words(&mantissa.to_be_bytes()));
+    vec
+}
+
+/// Sign of the mantissa and exponent, a 3-digit decimal exponent, one integer digit and 16 fraction digits
+fn packed(value: f64) -> Vec<u16> {
+    let sign = (value.is_sign_negative() as u32) << 31;
+
+    let (high, digits) = if value.is_infinite() {
+        (sign | 0x7FFF_0000, 0)
+    } else if value.is_nan() {
+        (0x7FFF_0000, u64::MAX)
+    } else if value == 0.0 {
+        (sign, 0)
+    } else {
+        // 17 significant digits are enough to represent any double exactly
+        let text = format!("{:.16e}", value.abs());
+        let (mantissa, exponent) = text.split_once('e').unwrap();
+        let exponent: i32 = exponent.parse().unwrap();
+        let mut mantissa = mantissa.bytes().filter(u8::is_ascii_digit).map(|digit| (digit - b'0') as u64);
+
+        let integer = mantissa.next().unwrap() as u32;
+        let digits = mantissa.fold(0, |digits, digit| (digits << 4) | digit);
+        let exponent_bcd = format!("{:03}", exponent.abs()).bytes().fold(0, |bcd, digit| (bcd << 4) | (digit - b'0') as u32);
+
+        (sign | ((exponent < 0) as u32) << 30 | exponent_bcd << 16 | integer, digits)
+    };
+
+    let mut vec = words(&high.to_be_bytes());
+    vec.extend(words(&digits.to_be_bytes()));
+    vec
+}
@@ -0,0 +1,94 @@
+use super::{strip_comment, Assembler, OpType};
+
+const BYTES_PER_ROW: usize = 8;
+
+/// A source or macro body line, as it was read
+#[derive(Debug)]
+pub struct ListingLine {
+    line: u32,
+    text: String,
+    depth: usize, // number of macro expansions and includes the line is in
+    first_token: usize, // tokens from here up to the next line's first token belong to this line
+}
+
+impl Assembler {
+    /// Records a line for the listing
+    pub(super) fn list_line(&mut self, line: u32, text: &str) {
+        let includes = std::iter::successors(self.files[self.file].1, |(file, _)| self.files[*file].1).count();
+
+        self.listing.push(ListingLine {
+            line,
+            text: text.trim_end().to_string(),
+            depth: self.expansion.len() + includes,
+            first_token: self.tokens.len(),
+        });
+    }
+
+    /// Every line with its address and the words it assembled to, followed by all labels and defines
+    pub fn listing(&self) -> String {
+        let mut out = String::new();
+
+        for (idx, line) in self.listing.iter().enumerate() {
+            let end = self.listing.get(idx + 1).map_or(self.tokens.len(), |next| next.first_token);
+
+            let bytes: Vec<u8> = (line.first_token .. end)
+                .filter(|&token| !matches!(self.tokens[token].op_type, OpType::Org(_)))
+                .flat_map(|token| self.bytes[self.output[token].clone()].iter().copied())
+                .collect();
+
+            // lines without code get the address of the code following them, like labels do
+            let location = match self.tokens.get(line.first_token) {
+                Some(token) => match token.op_type {
+                    OpType::Org(addr) | OpType::Rorg(addr) => addr,
+                    _ => token.location,
+                },
+
+                None => self.location,
+            };
+
+            let marker = match line.depth {
+                0 => String::new(),
+                depth => format!("{} ", "+".repeat(depth)),
+            };
+
+            let address = match strip_comment(&line.text).trim().is_empty() {
+                true  => String::new(),
+                false => format!("{location:08X}"),
+            };
+
+            let mut rows = bytes.chunks(BYTES_PER_ROW);
+            let first = format!("{:>6} {address:8}  {:20}  {marker}{}", line.line, hex_words(rows.next().unwrap_or(&[])), line.text);
+            out.push_str(first.trim_end());
+            out.push('\n');
+
+            for (row, bytes) in rows.enumerate() {
+                let address = location + ((row + 1) * BYTES_PER_ROW) as u32;
+                out.push_str(&format!("{:>6} {address:08X}  {}\n", "", hex_words(bytes)));
+            }
+        }
+
+        out.push_str("\nLabels\n");
+
+        for (name, addr) in self.symbols() {
+            out.push_str(&format!("{addr:08X}  {name}\n"));
+        }
+
+        out.push_str("\nDefines\n");
+
+        for (name, value) in self.define_values() {
+            let bang = match self.bang_defines.contains(name) {
+                true  => "!",
+                false => "",
+            };
+
+            out.push_str(&format!("{value:08X}  {bang}{name}\n"));
+        }
+
+        out
+    }
+}
+
+/// Bytes in hex, grouped into words
+fn hex_words(bytes: &[u8]) -> String {
+    bytes.chunks(2).map(|word| word.iter().map(|byte| format!("{byte:02X}")).collect::<String>()).collect::<Vec<_>>().join(" ")
+}
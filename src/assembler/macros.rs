@@ -0,0 +1,128 @@
+use crate::logging::Log;
+
+/// Maximum nesting depth of macro expansions, stops runaway recursion
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug)]
+pub struct Macro {
+    params: Vec<String>,
+    pub body: Vec<(u32, String)>, // source line, text
+}
+
+impl Macro {
+    pub fn new(params: Vec<String>) -> Self {
+        Self {
+            params,
+            body: Vec::new(),
+        }
+    }
+
+    /// Substitutes parameters into the macro body.
+    /// `\1`..`\9` are positional arguments, `\name` are named arguments,
+    /// `\0` is the size suffix of the call and `\@` is unique for each expansion.
+    pub fn expand(&self, size: Option<&str>, args: &[&str], id: u32) -> Result<Vec<(u32, String)>, Log> {
+        let mut values: Vec<String> = vec![String::new(); self.params.len()];
+        let mut positional = 0;
+
+        for &arg in args {
+            let named = arg.split_once('=').and_then(|(name, value)| {
+                let pos = self.params.iter().position(|p| p == name.trim())?;
+                Some((pos, value))
+            });
+
+            match named {
+                Some((pos, value)) if !value.starts_with('=') => values[pos] = value.trim().to_string(),
+
+                _ => {
+                    if !self.params.is_empty() && positional >= self.params.len() {
+                        return Err(Log::MacroTooManyArguments);
+                    }
+
+                    if positional >= values.len() {
+                        values.push(String::new());
+                    }
+
+                    values[positional] = arg.to_string();
+                    positional += 1;
+                }
+            }
+        }
+
+        let mut expanded = Vec::new();
+
+        for (line, text) in &self.body {
+            let mut out = String::new();
+            let mut chars = text.chars().peekable();
+            let mut quote = None; // the quote character of the string being copied
+
+            while let Some(c) = chars.next() {
+                if c != '\\' {
+                    match quote {
+                        None if c == '"' || c == '\'' => quote = Some(c),
+                        Some(q) if q == c => quote = None,
+                        _ => (),
+                    }
+
+                    out.push(c);
+                    continue;
+                }
+
+                match chars.peek() {
+                    Some('0') => {
+                        chars.next();
+                        out.push_str(size.unwrap_or(""));
+                    }
+
+                    Some(&d @ '1'..='9') => {
+                        chars.next();
+                        let idx = d as usize - '1' as usize;
+                        out.push_str(values.get(idx).map_or("", |v| v.as_str()));
+                    }
+
+                    Some('@') => {
+                        chars.next();
+                        out.push_str(&format!("_{id}"));
+                    }
+
+                    Some(d) if d.is_alphabetic() || *d == '_' => {
+                        let mut name = String::new();
+
+                        while let Some(&d) = chars.peek() {
+                            if !(d.is_alphanumeric() || d == '_') {
+                                break;
+                            }
+
+                            name.push(d);
+                            chars.next();
+                        }
+
+                        match self.params.iter().position(|p| *p == name) {
+                            Some(pos) => out.push_str(&values[pos]),
+
+                            // escape sequences like \n in strings aren't parameters
+                            None if quote.is_some() => {
+                                out.push('\\');
+                                out.push_str(&name);
+                            }
+
+                            None => return Err(Log::UnknownMacroParameter(name)),
+                        }
+                    }
+
+                    _ => {
+                        out.push('\\');
+
+                        // keeps an escaped quote from ending the string
+                        if quote.is_some() {
+                            out.extend(chars.next());
+                        }
+                    }
+                }
+            }
+
+            expanded.push((*line, out));
+        }
+
+        Ok(expanded)
+    }
+}
@@ -1,18 +1,23 @@
 #![allow(clippy::unusual_byte_groupings)]
 
 mod addressing;
+mod float;
 mod opsize;
 mod optype;
 mod value;
 mod constants;
+mod macros;
+mod listing;
 
-use crate::{logging::Log, assembler::{constants::*, addressing::ControlRegister}};
+use crate::{logging::{Diagnostic, Log, Logging, Severity}, assembler::{constants::*, addressing::ControlRegister}};
 
-use addressing::AddressingMode;
+use addressing::{AddressingMode, BitfieldPart};
+use listing::ListingLine;
+use macros::Macro;
 use opsize::OpSize;
 use optype::OpType;
 
-use std::collections::HashMap;
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}};
 
 use self::value::Value;
 
@@ -20,16 +25,63 @@ use self::value::Value;
 struct TokenizedString {
     opcode: String,
     size: Option<String>,
-    operands: [Option<String>; 2],
+    operands: Vec<String>,
 }
 
 #[derive(Debug)]
 struct Decoded { //todo: rename
     op_type: OpType,
     op_size: OpSize,
-    operands: [addressing::AddressingMode; 2],
+    operands: [addressing::AddressingMode; 3],
     line: u32,
-    location: u32,
+    column: u32,
+    file: usize,
+    location: u32, // logical address, used for labels
+    offset: u32,   // position in the output
+    expansion: Vec<(String, u32)>, // macro name, line in macro body
+}
+
+impl Decoded {
+    /// Size in bytes
+    fn len(&self) -> u32 {
+        match &self.op_type {
+            OpType::Data(size, values) => values.len() as u32 * size.len(),
+            OpType::Fill(size, count, _) => count * size.len(),
+            OpType::Binary(bytes) => bytes.len() as u32,
+            OpType::Align(align, offset, _) => (*offset as i64 - self.location as i64).rem_euclid(*align as i64) as u32,
+            OpType::Org(_) | OpType::Rorg(_) | OpType::End(_) | OpType::Define(_) => 0,
+            _ => 2 + self.op_type.extra_words(self.op_size, &self.operands) * 2 + AddressingMode::ea_size(&self.operands) as u32,
+        }
+    }
+
+    /// Source or destination format of an FPU instruction with an effective address.
+    /// Extended precision is the default, and data registers can only hold bytes, words, longs and singles
+    fn float_format(&self, ea: &AddressingMode) -> Result<u16, Log> {
+        let size = match self.op_size {
+            OpSize::Unsized => OpSize::X,
+            size => size,
+        };
+
+        match (ea, size) {
+            (AddressingMode::DataRegister(_), OpSize::D | OpSize::X | OpSize::P) => Err(Log::SizeOperandMismatch),
+            _ => Ok(size.float_format()),
+        }
+    }
+
+    /// Address of the word before an operand's extension words. Extension words come after the opcode
+    /// and any extra words of the instruction, in operand order
+    fn operand_location(&self, operand: usize) -> u32 {
+        let before: u32 = self.operands[.. operand].iter().map(|mode| mode.size() as u32).sum();
+        // wraps for tokens that relaxing pushed past the end of the address space, which are reported by `relax`
+        self.location.wrapping_add(self.op_type.extra_words(self.op_size, &self.operands) * 2 + before)
+    }
+}
+
+#[derive(Debug)]
+struct Conditional {
+    active: bool, // currently assembling this block
+    taken: bool,  // a block in this if/elseif/else chain has been assembled
+    has_else: bool,
 }
 
 #[derive(Default)]
@@ -37,6 +89,20 @@ pub enum CpuType {
     #[default] MC68000,
     MC68010,
     MC68020,
+    MC68030,
+    MC68040,
+    MC68060,
+    CPU32,
+    ColdFireA,
+    ColdFireB,
+    ColdFireC,
+}
+
+impl CpuType {
+    /// ColdFire ISA_A, ISA_B and ISA_C
+    pub fn is_coldfire(&self) -> bool {
+        matches!(self, CpuType::ColdFireA | CpuType::ColdFireB | CpuType::ColdFireC)
+    }
 }
 
 #[derive(Debug)]
@@ -46,6 +112,10 @@ pub enum DataType {
     Data24,
     Data32,
     Data64,
+    Single,
+    Double,
+    Extended,
+    Packed,
 }
 
 impl DataType {
@@ -59,362 +129,1422 @@ impl DataType {
             _ => None,
         }
     }
+
+    /// Size of `dc`, `ds` and `dcb`
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "b" => Some(Self::Data08),
+            "w" => Some(Self::Data16),
+            "l" => Some(Self::Data32),
+            "s" => Some(Self::Single),
+            "d" => Some(Self::Double),
+            "x" => Some(Self::Extended),
+            "p" => Some(Self::Packed),
+            _ => None,
+        }
+    }
+
+    fn len(&self) -> u32 {
+        match self {
+            Self::Data08 => 1,
+            Self::Data16 => 2,
+            Self::Data24 => 3,
+            Self::Data32 => 4,
+            Self::Data64 => 8,
+            Self::Single => 4,
+            Self::Double => 8,
+            Self::Extended | Self::Packed => 12,
+        }
+    }
+
+    /// Size of the floating point formats, for encoding
+    fn float_size(&self) -> Option<OpSize> {
+        match self {
+            Self::Single => Some(OpSize::S),
+            Self::Double => Some(OpSize::D),
+            Self::Extended => Some(OpSize::X),
+            Self::Packed => Some(OpSize::P),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct Assembler {
     tokens: Vec<Decoded>,
+    bytes: Vec<u8>,
     assembled: Vec<u16>,
     location: u32,
+    offset: u32,
     line: u32,
+    column: u32,
     labels: HashMap<String, u32>,
+    label_anchors: Vec<(String, usize)>, // label, index of the token following it
     last_label: String,
-    defines: HashMap<String, u64>,
+    defines: HashMap<String, Value>,
+    variables: HashSet<String>, // defines assigned with set
+    bang_defines: HashSet<String>, // defines written as !name, for the listing
+    macros: HashMap<String, Macro>,
+    macro_def: Option<(String, Macro, u32)>, // name, macro, nesting depth
+    expansion: Vec<(String, u32)>,
+    expansion_count: u32,
+    conditionals: Vec<Conditional>,
+    files: Vec<(String, Option<(usize, u32)>)>, // path, file and line it was included from
+    sources: Vec<String>, // text of each file, for showing source lines in diagnostics
+    file: usize,
+    include_stack: Vec<PathBuf>,
+    pub include_paths: Vec<PathBuf>,
     pub cpu_type: CpuType,
+    pub fpu: bool, // 68881/68882 instructions are enabled
+    pub logging: Logging,
+    pub segments: Vec<(u32, Vec<u8>)>, // output split at each org, address and bytes
+    pub start: Option<u32>, // address given to end
+    ended: bool,
+    listing: Vec<ListingLine>,
+    output: Vec<std::ops::Range<usize>>, // bytes in `bytes` for each token
+    pack_data: bool, // don't pad byte data to an even length
 }
 
 impl Assembler {
-    pub fn run(&mut self, text: &str) -> Result<&Vec<u16>, (Log, u32)> {
-        if let Err(e) = self.tokenize_string(text) {
-            return Err((e, self.line));
-        }
-
-        for token in &self.tokens {
-            // println!("{:?}", token);
+    pub fn run_file(&mut self, path: &str) -> Result<&Vec<u16>, Vec<Diagnostic>> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
 
-            match self.assemble(token) {
-                Ok(o) => self.assembled.extend(o),
-                Err(e) => return Err((e, token.line)),
+            Err(_) => {
+                self.logging.diagnostics.push(Diagnostic::new(Severity::Error, Log::FileNotFound(path.to_string()), "", 0, 0));
+                return Err(std::mem::take(&mut self.logging.diagnostics));
             }
+        };
+
+        self.files.push((path.to_string(), None));
+
+        if let Ok(canonical) = Path::new(path).canonicalize() {
+            self.include_stack.push(canonical);
         }
 
-        Ok(&self.assembled)
+        self.run(&text)
     }
 
-    fn tokenize_string(&mut self, text: &str) -> Result<(), Log> {
-        for lines in text.lines() {
-            self.line += 1;
+    /// Assembles the text, returning all errors and warnings if there were any errors.
+    /// Warnings are kept in `logging` otherwise
+    pub fn run(&mut self, text: &str) -> Result<&Vec<u16>, Vec<Diagnostic>> {
+        if self.files.is_empty() {
+            self.files.push((String::new(), None));
+        }
 
-            let trimmed_str = lines.split(';').next().unwrap().trim();
-            let separated_op: Vec<&str> = trimmed_str.splitn(2, ' ').collect();
+        self.sources.push(text.to_string());
+        self.tokenize_string(text);
+        self.relax();
 
-            if separated_op[0].is_empty() {
-                continue;
-            }
+        let mut warnings = Vec::new();
+        let mut segments = vec![(0, Vec::new())];
 
-            if let Some(data_type) = DataType::is_data(separated_op[0]) {
-                self.data_define(separated_op[1], data_type)?;
-                continue;
-            }
+        for token in &self.tokens {
+            // println!("{:?}", token);
+
+            match &token.op_type {
+                OpType::Org(addr) => segments.push((*addr, Vec::new())),
 
-            if let Some(label) = separated_op[0].strip_suffix(':') {
-                self.label_define(label)?;
-                continue;
+                OpType::End(Some(value)) => match value.resolve_value(&self.labels, &self.defines) {
+                    Ok(addr) if addr <= u32::MAX as u64 => self.start = Some(addr as u32),
+                    Ok(_) => self.logging.diagnostics.push(self.token_diagnostic(token, Severity::Error, Log::AddressOutOfRange)),
+                    Err(e) => self.logging.diagnostics.push(self.token_diagnostic(token, Severity::Error, e)),
+                },
+
+                _ => (),
             }
 
-            if let Some(define_name) = separated_op[0].strip_prefix('!') {
-                let define_val = separated_op[1].trim_start();
+            let start = self.bytes.len();
+
+            match self.emit(token, &mut warnings) {
+                Ok(o) => {
+                    // org padding only goes into the flat output
+                    if !matches!(token.op_type, OpType::Org(_)) {
+                        segments.last_mut().unwrap().1.extend(&o);
+                    }
 
-                if let Some(define_val) = define_val.strip_prefix('=') {
-                    let val = parse_n(define_val.trim_start())?;
-                    self.defines.insert(define_name.to_string(), val);
+                    self.bytes.extend(o);
                 }
 
-                continue;
+                Err(e) => self.logging.diagnostics.push(self.token_diagnostic(token, Severity::Error, e)),
             }
 
-            let (opcode, size) = if let Some((op, suffix)) = separated_op[0].split_once('.') {
-                (op.to_string(), Some(suffix.to_string()))
-            } else {
-                (separated_op[0].to_string(), None)
-            };
-
-            let mut operands = [None, None];
+            self.output.push(start .. self.bytes.len());
 
-            if separated_op.len() > 1 {
-                let commas: Vec<_> = separated_op[1].match_indices(',').collect();
+            for warning in warnings.drain(..) {
+                self.logging.diagnostics.push(self.token_diagnostic(token, Severity::Warning, warning));
+            }
+        }
 
-                if commas.is_empty() { //one operand
-                    operands[0] = Some(separated_op[1].trim_start().to_string());
-                } else { //one or two operands
-                    let parentheses = (separated_op[1].find('('), separated_op[1].find(')'));
+        segments.retain(|(_, bytes)| !bytes.is_empty());
+        self.segments = segments;
 
-                    match parentheses {
-                        (None, None) => { //separator comma
-                            let split_comma: Vec<&str> = separated_op[1].split(',').collect();
-                            operands[0] = Some(split_comma[0].trim().to_string());
-                            operands[1] = Some(split_comma[1].trim_start().to_string());
-                        }
+        self.assembled = self.bytes.chunks(2).map(|x| u16::from_be_bytes([x[0], *x.get(1).unwrap_or(&0)])).collect();
 
-                        (Some(paren0), Some(paren1)) => {
-                            for x in 0..commas.len() {
-                                let parentheses_pos = (
-                                    paren0 < commas[x].0,
-                                    paren1 < commas[x].0,
-                                );
-
-                                match parentheses_pos {
-                                    (true, true) | (false, false) => { // (), | ,() separator comma
-                                        let split_comma = separated_op[1].split_at(commas[x].0);
-                                        operands[0] = Some(split_comma.0.trim().to_string());
-                                        operands[1] = Some(split_comma.1[1..].trim_start().to_string());
-                                        break;
-                                    }
-
-                                    (true, false) => { // (,) inside parentheses, go to next comma
-                                        if x == commas.len() - 1 { //last comma inside parentheses, one operand
-                                            operands[0] = Some(separated_op[1].trim_start().to_string());
-                                        }
-                                    }
-
-                                    (false, true) => todo!("mismatched parentheses"), // ),( error
-                                }
-                            }
-                        }
+        match self.logging.has_errors() {
+            true  => Err(std::mem::take(&mut self.logging.diagnostics)),
+            false => Ok(&self.assembled),
+        }
+    }
 
-                        (Some(_), None) | (None, Some(_)) => todo!("mismatched parentheses"),
-                    }
-                }
-            }
+    /// All labels including sub labels, ordered by address
+    pub fn symbols(&self) -> Vec<(&str, u32)> {
+        let mut labels: Vec<_> = self.labels.iter().map(|(name, addr)| (name.as_str(), *addr)).collect();
+        labels.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(b.0)));
+        labels
+    }
 
-            let string_token = TokenizedString {
-                opcode,
-                size,
-                operands,
-            };
+    /// All defines, ordered by name
+    pub fn define_values(&self) -> Vec<(&str, u64)> {
+        let mut defines: Vec<_> = self.defines.keys()
+            .filter_map(|name| Some((name.as_str(), Value::Define(name.clone()).resolve_value(&self.labels, &self.defines).ok()?)))
+            .collect();
 
-            let token = self.string_token_to_token(&string_token, self.line, self.location)?;
+        defines.sort();
+        defines
+    }
 
-            self.location += match &token.op_type {
-                // OpType::Data(data) => data.len() as u32 * 2, //unused!
-                _ => 2 + AddressingMode::ea_size(&token.operands) as u32,
-            };
+    /// Creates a diagnostic at the position of a token, including the macro expansions it came from
+    fn token_diagnostic(&self, token: &Decoded, severity: Severity, e: Log) -> Diagnostic {
+        // the source line of an expanded token is the macro call, which has other operands
+        let (operand, e) = match e {
+            Log::InOperand(operand, e) => (Some(operand).filter(|_| token.expansion.is_empty()), *e),
+            e => (None, e),
+        };
 
-            self.tokens.push(token);
-        }
+        let log = token.expansion.iter().rev().fold(e, |e, (name, line)| {
+            Log::InMacro(name.clone(), *line, Box::new(e))
+        });
 
-        Ok(())
+        self.diagnostic(severity, log, token.file, token.line, token.column, operand)
     }
 
-    fn string_token_to_token(&mut self, tokens: &TokenizedString, line: u32, location: u32) -> Result<Decoded, Log> {
-        let opcode = OpType::parse_op(&tokens.opcode)?;
+    /// Records an error at the current line
+    fn error(&mut self, e: Log) {
+        let log = self.expansion.iter().rev().fold(e, |e, (name, line)| {
+            Log::InMacro(name.clone(), *line, Box::new(e))
+        });
 
-        let size = if let Some(size_suffix) = &tokens.size {
-            match size_suffix as &str {
-                "b" => OpSize::B,
-                "w" => OpSize::W,
-                "l" => OpSize::L,
-                _ => return Err(Log::InvalidSuffix),
-            }
-        } else {
-            OpSize::Unsized
-        };
+        let diagnostic = self.diagnostic(Severity::Error, log, self.file, self.line, self.column, None);
+        self.logging.diagnostics.push(diagnostic);
+    }
 
-        let extended_addressing = match self.cpu_type {
-            CpuType::MC68000 | CpuType::MC68010 => false,
-            _ => true,
+    /// Creates a diagnostic with the source line, the part of it to underline and notes.
+    /// Errors without a name or operand of their own underline `operand` if it's given, or the opcode
+    fn diagnostic(&self, severity: Severity, log: Log, file: usize, line: u32, column: u32, operand: Option<usize>) -> Diagnostic {
+        let source = match line {
+            0 => "",
+            _ => self.sources.get(file).and_then(|text| text.lines().nth(line as usize - 1)).unwrap_or(""),
         };
 
-        let mut modes = [AddressingMode::Empty, AddressingMode::Empty];
+        // the first word of the statement is the opcode, directive or macro name
+        let statement = source.get((column as usize).saturating_sub(1) ..).unwrap_or("");
+        let word = statement.split_whitespace().next().unwrap_or("");
+        let (mnemonic, suffix) = word.split_once('.').unwrap_or((word, ""));
 
-        for (x, mode) in modes.iter_mut().enumerate() {
-            *mode = match &tokens.operands[x] {
-                Some(operand) => addressing::determine_addressing_mode(operand, &opcode, size, &self.last_label, extended_addressing)?,
-                None => AddressingMode::Empty,
-            };
+        let mut notes = Vec::new();
 
-            if let AddressingMode::AddressRegister(_) = mode {
-                if size == OpSize::B {
-                    return Err(Log::AnB);
+        let (start, length) = match &log {
+            Log::InvalidSuffix | Log::UnsupportedSuffix | Log::CpuTypeSizeNotValid if !suffix.is_empty() => {
+                match (&log, OpType::parse_op(mnemonic)) {
+                    (Log::CpuTypeSizeNotValid, Ok(op_type)) if self.cpu_type.is_coldfire() => {
+                        notes.push(format!("valid sizes for `{mnemonic}` on ColdFire are: {}", op_type.cpu_sizes(&self.cpu_type).suffixes().join(", ")));
+                    }
+
+                    (Log::CpuTypeSizeNotValid, _) => notes.push(format!("`.{suffix}` requires a 68020 or later")),
+
+                    (_, Ok(op_type)) => notes.push(match op_type.sizes().suffixes().as_slice() {
+                        [] => format!("`{mnemonic}` does not take a size suffix"),
+                        suffixes => format!("valid sizes for `{mnemonic}` are: {}", suffixes.join(", ")),
+                    }),
+
+                    _ => (),
                 }
+
+                (mnemonic.len() + 1, suffix.len())
             }
-        }
 
-        Ok(Decoded {
-            op_type: opcode,
-            op_size: size,
-            operands: modes,
-            line: line,
-            location: location,
-        })
-    }
+            log => {
+                let operands = &statement[word.len() ..];
 
-    fn label_define(&mut self, label: &str) -> Result<(), Log> {
-        if label.starts_with('.') { // sub label
-            let sub_label = format!("{}{}", self.last_label, label);
-            
-            if !self.labels.contains_key(&sub_label) {
-                self.labels.insert(sub_label, self.location);
-            } else {
-                return Err(Log::LabelRedefinition);
-            }
-        } else {
-            if !self.labels.contains_key(label) {
-                self.last_label = label.to_string();
-                self.labels.insert(label.to_string(), self.location);
-            } else {
-                return Err(Log::LabelRedefinition);
+                // prefer a match in the operands so an operand that looks like the opcode is found
+                match log.highlight().filter(|text| !text.is_empty()) {
+                    Some(text) if operands.contains(text) => (word.len() + operands.find(text).unwrap(), text.len()),
+                    Some(text) if statement.contains(text) => (statement.find(text).unwrap(), text.len()),
+
+                    _ => match operand.and_then(|idx| split_list(strip_comment(operands)).ok()?.get(idx).copied()) {
+                        Some(text) if !text.is_empty() => (text.as_ptr() as usize - statement.as_ptr() as usize, text.len()),
+                        _ => (0, word.len()),
+                    },
+                }
             }
-        }
+        };
 
-        Ok(())
-    }
+        // errors in included files note where they were included from, innermost first
+        let mut parent = self.files[file].1;
 
-    fn data_define(&mut self, list: &str, size: DataType) -> Result<(), Log> {
-        let mut vec = Vec::new();
+        while let Some((file, line)) = parent {
+            notes.push(match self.files[file].0.as_str() {
+                "" => format!("included from line {line}"),
+                name => format!("included from {name}:{line}"),
+            });
 
-        for data in list.split(',').map(|x| x.trim()) {
-            vec.push(Value::new(data, &self.last_label));
+            parent = self.files[file].1;
         }
 
-        let mut len = vec.len() * match size {
-            DataType::Data08 => 1,
-            DataType::Data16 => 2,
-            DataType::Data24 => 3,
-            DataType::Data32 => 4,
-            DataType::Data64 => 8,
-        };
+        let mut diagnostic = Diagnostic::new(severity, log, &self.files[file].0, line, column);
 
-        if len & 1 != 0 {
-            // probably print a warning here
-            len += 1;
+        if !source.is_empty() {
+            diagnostic.source = source.to_string();
+            diagnostic.span = Some((column + start as u32, length as u32));
         }
 
-        self.tokens.push(Decoded {
-            op_type: OpType::Data(size, vec),
-            op_size: OpSize::Unsized,
-            operands: [AddressingMode::Empty, AddressingMode::Empty],
-            line: self.line,
-            location: self.location,
-        });
-
-        self.location += len as u32;
-
-        Ok(())
+        diagnostic.notes = notes;
+        diagnostic
     }
 
-    fn assemble(&self, op: &Decoded) -> Result<Vec<u16>, Log> {
-        use OpSize::*;
-        use OpType::*;
+    /// Assigns addresses to all tokens and labels.
+    /// Returns the first token that goes beyond the end of the address space, if any
+    fn layout(&mut self) -> Option<usize> {
+        let (mut location, mut offset) = (0u32, 0u32);
+        let mut overflow = None;
 
-        op.op_type.valid_size(op.op_size)?;
+        for (idx, token) in self.tokens.iter_mut().enumerate() {
+            token.location = location;
+            token.offset = offset;
 
-        op.op_type.is_valid_modes(&op.operands)?;
+            match token.op_type {
+                OpType::Org(addr) => { //going backwards is reported when assembling
+                    location = addr;
+                    offset = offset.max(addr);
+                }
 
-        let (ea_a1, ea_a2) = op.operands[0].effective_addressing(&self.labels, &self.defines, op.location)?;
-        let (ea_b1, ea_b2) = op.operands[1].effective_addressing(&self.labels, &self.defines, op.location)?;
+                OpType::Rorg(addr) => location = addr,
 
-        Ok(match &op.op_type {
-            Branch(_) => {
-                match op.op_size {
-                    B => vec![op.op_type.format() | (ea_a2[0] & 0xFF)],
-                    W => vec![op.op_type.format(), ea_a2[0]],
-                    _ => unreachable!(),
-                }
-            }
+                _ => {
+                    let len = token.len();
 
-            Rtd => {
-                match self.cpu_type {
-                    CpuType::MC68000 => return Err(Log::UnsupportedInstruction),
-                    _ => {
-                        vec![op.op_type.format(), ea_a2[0]]
+                    match (location.checked_add(len), offset.checked_add(len)) {
+                        (Some(next_location), Some(next_offset)) => (location, offset) = (next_location, next_offset),
+                        _ => overflow = overflow.or(Some(idx)),
                     }
                 }
             }
+        }
 
-            NoOperands(_) => vec![op.op_type.format()],
+        self.location = location;
+        self.offset = offset;
 
-            Immediates(_) => {
-                let ea = match ea_b1 {
-                    CCR_MASK => {
-                        if op.op_size == B {
-                            IMMEDIATE_MASK
-                        } else {
-                            todo!("error")
-                        }
-                    }
+        for (label, idx) in &self.label_anchors {
+            let addr = match self.tokens.get(*idx) {
+                Some(token) => token.location,
+                None => location,
+            };
 
-                    SR_MASK => {
-                        if op.op_size == W {
-                            IMMEDIATE_MASK
-                        } else {
-                            todo!("error")
+            self.labels.insert(label.clone(), addr);
+        }
+
+        overflow
+    }
+
+    /// Picks the smallest size for unsized branches, and for absolute addresses and displacements without a size.
+    /// Growing one moves the code after it, which can push others out of range, so this repeats until nothing changes size
+    fn relax(&mut self) {
+        let extended_addressing = !matches!(self.cpu_type, CpuType::MC68000 | CpuType::MC68010);
+        // CPU32 and ColdFire have scaled indexes, but not the full extension word formats. ColdFire ISA_A has no long branches
+        let full_format = extended_addressing && !matches!(self.cpu_type, CpuType::CPU32) && !self.cpu_type.is_coldfire();
+        let long_branches = extended_addressing && !matches!(self.cpu_type, CpuType::ColdFireA);
+
+        loop {
+            let overflow = self.layout();
+            let mut changed = false;
+
+            for idx in 0..self.tokens.len() {
+                for operand in 0..3 {
+                    let token = &self.tokens[idx];
+                    let location = token.operand_location(operand);
+
+                    let (OpType::Branch(_) | OpType::FBranch(_), OpSize::Unsized, AddressingMode::BranchDisplacement(size, disp)) = (&token.op_type, token.op_size, &token.operands[operand]) else {
+                        let mut mode = std::mem::replace(&mut self.tokens[idx].operands[operand], AddressingMode::Empty);
+                        changed |= mode.grow(&self.labels, &self.defines, location, full_format);
+                        self.tokens[idx].operands[operand] = mode;
+                        continue;
+                    };
+
+                    let Ok(disp) = addressing::displacement(disp, &self.labels, &self.defines, location, true) else {
+                        continue; //reported when assembling
+                    };
+
+                    let needed = if addressing::branch_fits(OpSize::B, disp) {
+                        OpSize::B
+                    } else if addressing::branch_fits(OpSize::W, disp) || !long_branches {
+                        OpSize::W
+                    } else {
+                        OpSize::L
+                    };
+
+                    if let (OpSize::B, OpSize::W | OpSize::L) | (OpSize::W, OpSize::L) = (*size, needed) {
+                        if let AddressingMode::BranchDisplacement(size, _) = &mut self.tokens[idx].operands[operand] {
+                            *size = needed;
                         }
+
+                        changed = true;
                     }
+                }
+            }
 
-                    _ => ea_b1,
-                };
+            if !changed {
+                if let Some(idx) = overflow {
+                    self.logging.diagnostics.push(self.token_diagnostic(&self.tokens[idx], Severity::Error, Log::AddressOutOfRange));
+                }
 
-                let mut format = vec![op.op_type.format() | op.op_size.size1() | ea];
-                format.extend(ea_a2);
-                format.extend(ea_b2);
-                format
+                return;
             }
+        }
+    }
 
-            AddSub(_) | OrAnd(_) => {
-                let (reg, dir, ea) = match ea_b1 & MODE_MASK == 0b000_000 {
-                    true => (ea_b1 & 0b111, 0, (ea_a1, ea_a2)),
-                    false => (ea_a1 & 0b111, 1 << 8, (ea_b1, ea_b2)),
-                };
+    fn tokenize_string(&mut self, text: &str) {
+        let conditional_depth = self.conditionals.len();
 
-                let mut format = vec![op.op_type.format() | (reg << 9) | dir | op.op_size.size1() | ea.0];
-                format.extend(ea.1);
-                format
+        for lines in text.lines() {
+            if self.ended {
+                break;
             }
 
-            Eor => {
-                let reg = ea_a1 & 0b111;
-                let mut format = vec![op.op_type.format() | (reg << 9) | op.op_size.size1() | ea_b1];
-                format.extend(ea_b2);
-                format
+            self.line += 1;
+            self.column = (lines.len() - lines.trim_start().len()) as u32 + 1;
+            self.list_line(self.line, lines);
+
+            if let Err(e) = self.tokenize_line(lines) {
+                self.error(e);
             }
+        }
 
-            AddSubA(_) => {
-                let reg = (ea_b1 & 0b111) << 9;
+        if self.macro_def.take().is_some() {
+            self.error(Log::UnterminatedMacro);
+        }
 
-                let opmode = match op.op_size {
-                    OpSize::W => 0b011 << 6,
-                    OpSize::L => 0b111 << 6,
-                    _ => unreachable!(),
-                };
+        if self.conditionals.len() != conditional_depth {
+            self.conditionals.truncate(conditional_depth);
+            self.error(Log::UnterminatedIf);
+        }
+    }
 
-                let mut format = vec![op.op_type.format() | reg | opmode | ea_a1];
-                format.extend(ea_a2);
-                format
-            }
+    fn tokenize_line(&mut self, line: &str) -> Result<(), Log> {
+        let trimmed_str = strip_comment(line).trim();
+        let separated_op: Vec<&str> = trimmed_str.splitn(2, ' ').collect();
 
-            AddSubX(_) | Bcd(_) => {
-                let rm = match ea_a1 & MODE_MASK == 0b000_000 {
-                    true => 0,
-                    false => 1 << 3,
-                };
+        if self.macro_def.is_some() {
+            let body_line = self.body_line();
+            let (_, mac, depth) = self.macro_def.as_mut().unwrap();
 
-                let rx = (ea_a1 & 0b111) << 9;
-                let ry = ea_b1 & 0b111;
+            match separated_op[0] {
+                "macro" => *depth += 1,
 
-                vec![op.op_type.format() | rx | (1 << 8) | rm | ry]
+                "endm" if *depth == 0 => {
+                    let (name, mac, _) = self.macro_def.take().unwrap();
+                    self.macros.entry(name).or_insert(mac);
+                    return Ok(());
+                }
+
+                "endm" => *depth -= 1,
+                _ => (),
             }
 
-            AddSubQ(_) => {
-                let imm = ea_a2[0];
+            mac.body.push((body_line, trimmed_str.to_string()));
+            return Ok(());
+        }
 
-                if !(1..=8).contains(&imm) {
-                    todo!("addq/subq immediate out of range")
-                }
+        if separated_op[0].is_empty() {
+            return Ok(());
+        }
 
-                if ea_b1 & MODE_MASK == ADDRESS_REGISTER_MASK && op.op_size == OpSize::W {
-                    println!("info: addq.w/subq.w will operate on the entire address register");
-                }
+        if self.conditional(separated_op[0], separated_op.get(1).copied().unwrap_or(""))? {
+            return Ok(());
+        }
 
-                let mut format = vec![op.op_type.format() | ((imm & 0b111) << 9) | op.op_size.size1() | ea_b1];
-                format.extend(ea_b2);
-                format
+        if self.conditionals.iter().any(|c| !c.active) {
+            return Ok(());
+        }
+
+        if separated_op[0] == "macro" {
+            return self.macro_define(separated_op.get(1).copied().unwrap_or(""));
+        }
+
+        if separated_op[0] == "endm" {
+            return Err(Log::UnexpectedEndm);
+        }
+
+        if let Some(data_type) = DataType::is_data(separated_op[0]) {
+            return self.data_define(separated_op.get(1).copied().unwrap_or(""), data_type);
+        }
+
+        let (directive, suffix) = separated_op[0].split_once('.').unwrap_or((separated_op[0], "w"));
+
+        if matches!(directive, "dc" | "ds" | "dcb") {
+            let size = DataType::from_suffix(suffix).ok_or(Log::InvalidSuffix)?;
+            let args = separated_op.get(1).copied().unwrap_or("");
+
+            return match directive {
+                "dc" => self.data_define(args, size),
+                "ds" => self.data_block(args, size, false),
+                _ => self.data_block(args, size, true),
+            };
+        }
+
+        if let Some((name, reassignable, expr)) = assignment(trimmed_str) {
+            self.assign(name, reassignable, expr)?;
+
+            if trimmed_str.starts_with('!') {
+                self.bang_defines.insert(name.to_string());
             }
 
-            Jump(_) | Tas | Pea | Scc(_) | Nbcd => {
-                let mut format = vec![op.op_type.format() | ea_a1];
-                format.extend(ea_a2);
-                format
+            return Ok(());
+        }
+
+        match separated_op[0] {
+            "pstring" => return self.string_define(separated_op.get(1).copied().unwrap_or(""), true),
+            "cstring" => return self.string_define(separated_op.get(1).copied().unwrap_or(""), false),
+            _ => (),
+        }
+
+        match separated_op[0] {
+            "even" => return self.align("2"),
+            "align" => return self.align(separated_op.get(1).copied().unwrap_or("")),
+            "cnop" => return self.cnop(separated_op.get(1).copied().unwrap_or("")),
+
+            "autopad" => {
+                self.pack_data = match separated_op.get(1).map(|arg| arg.trim()) {
+                    Some("on") => false,
+                    Some("off") => true,
+                    arg => return Err(Log::InvalidArgument(arg.unwrap_or("").to_string(), "on or off")),
+                };
+
+                return Ok(());
+            }
+
+            "include" => return self.include(separated_op.get(1).copied().unwrap_or("")),
+            "incbin" => return self.incbin(separated_op.get(1).copied().unwrap_or("")),
+            "org" => return self.org(separated_op.get(1).copied().unwrap_or("")),
+            "rorg" | "base" => return self.rorg(separated_op.get(1).copied().unwrap_or("")),
+            "end" => return self.end(separated_op.get(1).copied().unwrap_or("")),
+            _ => (),
+        }
+
+        if let Some(label) = separated_op[0].strip_suffix(':') {
+            return self.label_define(label);
+        }
+
+        if separated_op[0].starts_with('!') {
+            return Err(Log::InvalidDefine(trimmed_str.to_string()));
+        }
+
+        let (opcode, size) = if let Some((op, suffix)) = separated_op[0].split_once('.') {
+            (op.to_string(), Some(suffix.to_string()))
+        } else {
+            (separated_op[0].to_string(), None)
+        };
+
+        if self.macros.contains_key(&opcode) {
+            return self.macro_expand(&opcode, size.as_deref(), separated_op.get(1).copied().unwrap_or(""));
+        }
+
+        let operands = match separated_op.get(1) {
+            Some(list) => split_list(list)?.iter().map(|text| text.to_string()).collect(),
+            None => vec![],
+        };
+
+        let string_token = TokenizedString {
+            opcode,
+            size,
+            operands,
+        };
+
+        let token = self.string_token_to_token(&string_token, self.line, self.location)?;
+
+        self.advance(token.len())?;
+
+        self.tokens.push(token);
+
+        Ok(())
+    }
+
+    /// Moves past a token, which must not go beyond the end of the address space
+    fn advance(&mut self, len: u32) -> Result<(), Log> {
+        match (self.location.checked_add(len), self.offset.checked_add(len)) {
+            (Some(location), Some(offset)) => {
+                self.location = location;
+                self.offset = offset;
+                Ok(())
+            }
+
+            _ => Err(Log::AddressOutOfRange),
+        }
+    }
+
+    /// Sets the address of the following code, padding the output up to it
+    fn org(&mut self, arg: &str) -> Result<(), Log> {
+        let addr = self.resolve_address(arg)?;
+
+        self.push_directive(OpType::Org(addr))?;
+        self.location = addr;
+        self.offset = addr;
+
+        Ok(())
+    }
+
+    /// Sets the address of the following code without moving its position in the output
+    fn rorg(&mut self, arg: &str) -> Result<(), Log> {
+        let addr = self.resolve_address(arg)?;
+
+        self.push_directive(OpType::Rorg(addr))?;
+        self.location = addr;
+
+        Ok(())
+    }
+
+    fn resolve_address(&self, arg: &str) -> Result<u32, Log> {
+        if arg.trim().is_empty() {
+            return Err(Log::MissingAddress);
+        }
+
+        let addr = self.resolve_now(arg.trim())?;
+
+        if addr > u32::MAX as u64 {
+            return Err(Log::AddressOutOfRange);
+        }
+
+        if addr & 1 != 0 {
+            return Err(Log::OddAddress);
+        }
+
+        Ok(addr as u32)
+    }
+
+    /// Ends the source, the rest of it is ignored. An optional address is where execution starts
+    fn end(&mut self, arg: &str) -> Result<(), Log> {
+        let start = match arg.trim() {
+            "" => None,
+            arg => Some(Value::new(arg, &self.last_label)?),
+        };
+
+        self.push_directive(OpType::End(start))?;
+        self.ended = true;
+
+        Ok(())
+    }
+
+    /// Current source line, taking macro expansions into account
+    fn body_line(&self) -> u32 {
+        match self.expansion.last() {
+            Some((_, line)) => *line,
+            None => self.line,
+        }
+    }
+
+    fn macro_define(&mut self, header: &str) -> Result<(), Log> {
+        let (name, params) = match header.trim().split_once(' ') {
+            Some((name, params)) => (name, split_list(params)?),
+            None => (header.trim(), Vec::new()),
+        };
+
+        if name.is_empty() {
+            return Err(Log::MissingMacroName);
+        }
+
+        let params = params.iter().map(|p| p.to_string()).collect();
+        self.macro_def = Some((name.to_string(), Macro::new(params), 0));
+
+        // the body is still read up to its endm, then thrown away
+        match self.macros.contains_key(name) {
+            true  => Err(Log::MacroRedefinition),
+            false => Ok(()),
+        }
+    }
+
+    fn macro_expand(&mut self, name: &str, size: Option<&str>, args: &str) -> Result<(), Log> {
+        if self.expansion.len() >= macros::MAX_DEPTH {
+            return Err(Log::MacroDepthExceeded);
+        }
+
+        self.expansion_count += 1;
+        let lines = self.macros[name].expand(size, &split_list(args)?, self.expansion_count)?;
+        let conditional_depth = self.conditionals.len();
+
+        for (body_line, text) in lines {
+            if self.ended {
+                break;
+            }
+
+            self.expansion.push((name.to_string(), body_line));
+            self.list_line(body_line, &text);
+
+            match self.tokenize_line(&text) {
+                Ok(()) => (),
+
+                // unwinds every expansion so it's reported once, at the outermost call
+                Err(Log::MacroDepthExceeded) => {
+                    self.expansion.pop();
+                    self.conditionals.truncate(conditional_depth);
+                    return Err(Log::MacroDepthExceeded);
+                }
+
+                Err(e) => self.error(e),
+            }
+
+            self.expansion.pop();
+        }
+
+        if self.conditionals.len() != conditional_depth {
+            self.conditionals.truncate(conditional_depth);
+            return Err(Log::UnterminatedIf);
+        }
+
+        Ok(())
+    }
+
+    /// Handles if/elseif/else/endif/ifdef/ifndef. Returns false if the directive isn't a conditional
+    fn conditional(&mut self, directive: &str, arg: &str) -> Result<bool, Log> {
+        let arg = arg.trim();
+        let parent_active = self.conditionals.iter().all(|c| c.active);
+
+        match directive {
+            "if" | "ifdef" | "ifndef" => {
+                let condition = match directive {
+                    "if" if parent_active => self.evaluate_condition(arg),
+                    "ifdef" => Ok(self.defines.contains_key(arg.trim_start_matches('!'))),
+                    "ifndef" => Ok(!self.defines.contains_key(arg.trim_start_matches('!'))),
+                    _ => Ok(false),
+                };
+
+                // push the block even if the condition is invalid, to keep else/endif balanced
+                let active = parent_active && *condition.as_ref().unwrap_or(&false);
+
+                self.conditionals.push(Conditional {
+                    active,
+                    taken: active || !parent_active,
+                    has_else: false,
+                });
+
+                condition?;
+            }
+
+            "elseif" | "else" => {
+                let parent_active = self.conditionals.iter().rev().skip(1).all(|c| c.active);
+
+                let taken = match self.conditionals.last() {
+                    Some(Conditional { has_else: true, .. }) => return Err(Log::UnexpectedElse),
+                    Some(c) => c.taken,
+                    None => return Err(Log::UnexpectedElse),
+                };
+
+                let active = !taken && parent_active && match directive {
+                    "elseif" => self.evaluate_condition(arg)?,
+                    _ => true,
+                };
+
+                let cond = self.conditionals.last_mut().unwrap();
+                cond.active = active;
+                cond.taken |= active;
+                cond.has_else = directive == "else";
+            }
+
+            "endif" => {
+                if self.conditionals.pop().is_none() {
+                    return Err(Log::UnexpectedEndif);
+                }
+            }
+
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    fn evaluate_condition(&self, expr: &str) -> Result<bool, Log> {
+        if expr.is_empty() {
+            return Err(Log::MissingCondition);
+        }
+
+        Ok(self.resolve_now(expr)? != 0)
+    }
+
+    /// Resolves an expression using only the labels and defines seen so far
+    fn resolve_now(&self, expr: &str) -> Result<u64, Log> {
+        Value::new(expr, &self.last_label)?.resolve_value(&self.labels, &self.defines)
+    }
+
+    /// Looks for a file next to the current file first, then in the include paths
+    fn find_file(&self, name: &str) -> Result<PathBuf, Log> {
+        let current_dir = Path::new(&self.files[self.file].0).parent().unwrap_or(Path::new(""));
+
+        std::iter::once(current_dir)
+            .chain(self.include_paths.iter().map(|p| p.as_path()))
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+            .ok_or(Log::FileNotFound(name.to_string()))
+    }
+
+    fn include(&mut self, arg: &str) -> Result<(), Log> {
+        let name = parse_file_name(arg)?;
+        let path = self.find_file(name)?;
+
+        let canonical = path.canonicalize().map_err(|_| Log::FileNotFound(name.to_string()))?;
+
+        if self.include_stack.contains(&canonical) {
+            return Err(Log::IncludeCycle(name.to_string()));
+        }
+
+        let text = std::fs::read_to_string(&path).map_err(|_| Log::FileNotFound(name.to_string()))?;
+
+        self.files.push((path.display().to_string(), Some((self.file, self.line))));
+        self.sources.push(text.clone());
+        self.include_stack.push(canonical);
+
+        let (file, line) = (self.file, self.line);
+        self.file = self.files.len() - 1;
+        self.line = 0;
+
+        self.tokenize_string(&text);
+
+        self.include_stack.pop();
+        self.file = file;
+        self.line = line;
+
+        Ok(())
+    }
+
+    fn incbin(&mut self, args: &str) -> Result<(), Log> {
+        let args = split_list(args)?;
+        let name = parse_file_name(args.first().copied().unwrap_or(""))?;
+        let path = self.find_file(name)?;
+
+        let data = std::fs::read(path).map_err(|_| Log::FileNotFound(name.to_string()))?;
+
+        let offset = match args.get(1) {
+            Some(offset) => self.resolve_now(offset)? as usize,
+            None => 0,
+        };
+
+        let length = match args.get(2) {
+            Some(length) => self.resolve_now(length)? as usize,
+            None => data.len().saturating_sub(offset),
+        };
+
+        let bytes = match data.get(offset..offset.saturating_add(length)) {
+            Some(bytes) => bytes.to_vec(),
+            None => return Err(Log::IncbinOutOfRange),
+        };
+
+        self.push_data(OpType::Binary(bytes))?;
+
+        Ok(())
+    }
+
+    fn string_token_to_token(&mut self, tokens: &TokenizedString, line: u32, location: u32) -> Result<Decoded, Log> {
+        let opcode = OpType::parse_op(&tokens.opcode)?.for_cpu(&self.cpu_type);
+
+        let size = if let Some(size_suffix) = &tokens.size {
+            match size_suffix as &str {
+                "b" => OpSize::B,
+                "s" if matches!(opcode, OpType::Branch(_)) => OpSize::B,
+                "w" => OpSize::W,
+                "l" => OpSize::L,
+                "s" => OpSize::S,
+                "d" => OpSize::D,
+                "x" => OpSize::X,
+                "p" => OpSize::P,
+                _ => return Err(Log::InvalidSuffix),
+            }
+        } else {
+            OpSize::Unsized
+        };
+
+        let extended_addressing = !matches!(self.cpu_type, CpuType::MC68000 | CpuType::MC68010);
+
+        if let OpType::Mac(_) = opcode {
+            let modes = addressing::mac_operands(&tokens.operands, &opcode, size, &self.last_label, extended_addressing)?;
+            return Ok(self.decoded(opcode, size, modes, line, location));
+        }
+
+        if let Some(extra) = tokens.operands.get(3) {
+            return Err(Log::ExtraOperand(extra.to_string()));
+        }
+
+        if let (Some(extra), false) = (tokens.operands.get(2), opcode.has_third_operand()) {
+            return Err(Log::ExtraOperand(extra.to_string()));
+        }
+
+        let mut modes = [AddressingMode::Empty, AddressingMode::Empty, AddressingMode::Empty];
+        let mut bitfield = None;
+
+        // {offset:width} goes on the effective address, which is the first operand except for bfins
+        let bitfield_operand = match opcode {
+            OpType::Bitfield(0b111) => Some(1),
+            OpType::Bitfield(_) => Some(0),
+            _ => None,
+        };
+
+        // EMAC registers are only recognized where they can be used, elsewhere their names are labels
+        let mac_registers = self.cpu_type.is_coldfire() && matches!(opcode, OpType::Move | OpType::Movclr);
+
+        for (x, mode) in modes.iter_mut().enumerate() {
+            let mac_register = tokens.operands.get(x).filter(|_| mac_registers).and_then(|operand| addressing::mac_register(operand));
+
+            *mode = match tokens.operands.get(x) {
+                Some(_) if mac_register.is_some() => mac_register.unwrap(),
+
+                Some(operand) if bitfield_operand == Some(x) => {
+                    let (ea, field) = addressing::bitfield(operand, &self.last_label)?;
+                    bitfield = Some(field);
+                    addressing::determine_addressing_mode(ea, &opcode, size, &self.last_label, extended_addressing)?
+                }
+
+                Some(operand) => addressing::determine_addressing_mode(operand, &opcode, size, &self.last_label, extended_addressing)?,
+                None => AddressingMode::Empty,
+            };
+
+            if let AddressingMode::AddressRegister(_) = mode {
+                if size == OpSize::B {
+                    return Err(Log::AnB);
+                }
+            }
+
+            if let (CpuType::CPU32, true) = (&self.cpu_type, mode.is_full_format()) {
+                return Err(Log::CpuTypeModeNotValid);
+            }
+
+            if self.cpu_type.is_coldfire() && !mode.is_coldfire() {
+                return Err(Log::CpuTypeModeNotValid);
+            }
+        }
+
+        // the bitfield is kept as an operand following the others
+        if let (Some(field), Some(slot)) = (bitfield, modes.iter_mut().find(|mode| **mode == AddressingMode::Empty)) {
+            *slot = field;
+        }
+
+        Ok(self.decoded(opcode, size, modes, line, location))
+    }
+
+    fn decoded(&self, op_type: OpType, op_size: OpSize, operands: [AddressingMode; 3], line: u32, location: u32) -> Decoded {
+        Decoded {
+            op_type,
+            op_size,
+            operands,
+            line,
+            column: self.column,
+            file: self.file,
+            location,
+            offset: self.offset,
+            expansion: self.expansion.clone(),
+        }
+    }
+
+    fn label_define(&mut self, label: &str) -> Result<(), Log> {
+        if label.starts_with('.') { // sub label
+            let sub_label = format!("{}{}", self.last_label, label);
+            
+            if !self.labels.contains_key(&sub_label) {
+                self.labels.insert(sub_label.clone(), self.location);
+                self.label_anchors.push((sub_label, self.tokens.len()));
+            } else {
+                return Err(Log::LabelRedefinition);
+            }
+        } else {
+            if !self.labels.contains_key(label) {
+                self.last_label = label.to_string();
+                self.labels.insert(label.to_string(), self.location);
+                self.label_anchors.push((label.to_string(), self.tokens.len()));
+            } else {
+                return Err(Log::LabelRedefinition);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn data_define(&mut self, list: &str, size: DataType) -> Result<(), Log> {
+        let vec = self.data_values(list, &size)?;
+        self.push_data(OpType::Data(size, vec))?;
+
+        Ok(())
+    }
+
+    /// Adds data, followed by padding to an even address unless data is packed
+    fn push_data(&mut self, op_type: OpType) -> Result<(), Log> {
+        self.push_directive(op_type)?;
+
+        if !self.pack_data {
+            self.push_directive(OpType::Align(2, 0, 0))?;
+        }
+
+        Ok(())
+    }
+
+    /// `align n[, fill]` pads up to a multiple of n, which must be a power of two
+    fn align(&mut self, args: &str) -> Result<(), Log> {
+        let (align, fill) = match split_list(args)?[..] {
+            [align] => (self.resolve_now(align)?, 0),
+            [align, fill] => (self.resolve_now(align)?, self.resolve_now(fill)?),
+            [] => return Err(Log::MissingValue),
+            [_, _, extra, ..] => return Err(Log::ExtraOperand(extra.to_string())),
+        };
+
+        if !align.is_power_of_two() || align > 1 << 31 {
+            return Err(Log::InvalidAlignment(align));
+        }
+
+        if fill > 0xFF {
+            return Err(Log::ValueOutOfRange(fill as i64, 0, 0xFF));
+        }
+
+        self.push_directive(OpType::Align(align as u32, 0, fill as u8))?;
+
+        Ok(())
+    }
+
+    /// `cnop offset, align` pads up to the next multiple of align, plus offset
+    fn cnop(&mut self, args: &str) -> Result<(), Log> {
+        let (offset, align) = match split_list(args)?[..] {
+            [offset, align] => (self.resolve_now(offset)?, self.resolve_now(align)?),
+            [_] | [] => return Err(Log::MissingValue),
+            [_, _, extra, ..] => return Err(Log::ExtraOperand(extra.to_string())),
+        };
+
+        if align == 0 || align > 1 << 31 {
+            return Err(Log::InvalidAlignment(align));
+        }
+
+        self.push_directive(OpType::Align(align as u32, (offset % align) as u32, 0))?;
+
+        Ok(())
+    }
+
+    /// `ds` reserves zero filled space, `dcb` repeats a value
+    fn data_block(&mut self, args: &str, size: DataType, has_value: bool) -> Result<(), Log> {
+        let args = split_list(args)?;
+
+        let value = match (has_value, args.get(1)) {
+            (false, None) => Value::Number(0),
+            (true, Some(value)) => Value::new(value, &self.last_label)?,
+            (true, None) => return Err(Log::MissingValue),
+            (false, Some(extra)) => return Err(Log::ExtraOperand(extra.to_string())),
+        };
+
+        if let Some(extra) = args.get(2) {
+            return Err(Log::ExtraOperand(extra.to_string()));
+        }
+
+        let count = self.resolve_now(args.first().copied().unwrap_or(""))?;
+
+        if count.saturating_mul(size.len() as u64) > u32::MAX as u64 {
+            return Err(Log::AddressOutOfRange);
+        }
+
+        self.push_data(OpType::Fill(size, count as u32, value))?;
+
+        Ok(())
+    }
+
+    /// Defines a name for a value. Names assigned with `set` can be assigned again, others can't.
+    /// Values from `set` are resolved right away since they depend on where they are assigned,
+    /// others are resolved when used and checked once all labels are known
+    fn assign(&mut self, name: &str, reassignable: bool, expr: &str) -> Result<(), Log> {
+        if self.defines.contains_key(name) && !(reassignable && self.variables.contains(name)) {
+            return Err(Log::DefineRedefinition(name.to_string()));
+        }
+
+        let value = match reassignable {
+            true  => Value::Number(self.resolve_now(expr)?),
+            false => Value::new(expr, &self.last_label)?,
+        };
+
+        if reassignable {
+            self.variables.insert(name.to_string());
+        } else {
+            self.push_directive(OpType::Define(name.to_string()))?;
+        }
+
+        self.defines.insert(name.to_string(), value);
+
+        Ok(())
+    }
+
+    /// Byte data with a length byte in front (`pstring`) or a zero byte after (`cstring`)
+    fn string_define(&mut self, list: &str, length_prefix: bool) -> Result<(), Log> {
+        let mut vec = self.data_values(list, &DataType::Data08)?;
+
+        match length_prefix {
+            true => match u8::try_from(vec.len()) {
+                Ok(len) => vec.insert(0, Value::Number(len as u64)),
+                Err(_) => return Err(Log::StringTooLong(vec.len())),
+            },
+
+            false => vec.push(Value::Number(0)),
+        }
+
+        self.push_data(OpType::Data(DataType::Data08, vec))?;
+
+        Ok(())
+    }
+
+    /// Values of a data list, strings are split up into one value per byte
+    fn data_values(&self, list: &str, size: &DataType) -> Result<Vec<Value>, Log> {
+        let mut vec = Vec::new();
+
+        for data in split_list(list)? {
+            if data.starts_with('"') {
+                if !matches!(size, DataType::Data08) {
+                    return Err(Log::StringNotBytes);
+                }
+
+                vec.extend(parse_string(data)?.into_iter().map(|byte| Value::Number(byte as u64)));
+            } else {
+                vec.push(Value::new(data, &self.last_label)?);
+            }
+        }
+
+        Ok(vec)
+    }
+
+    /// Adds a token for a directive that has no size suffix or addressing modes
+    fn push_directive(&mut self, op_type: OpType) -> Result<(), Log> {
+        let token = Decoded {
+            op_type,
+            op_size: OpSize::Unsized,
+            operands: [AddressingMode::Empty, AddressingMode::Empty, AddressingMode::Empty],
+            line: self.line,
+            column: self.column,
+            file: self.file,
+            location: self.location,
+            offset: self.offset,
+            expansion: self.expansion.clone(),
+        };
+
+        self.advance(token.len())?;
+        self.tokens.push(token);
+
+        Ok(())
+    }
+
+    /// Bytes for a token, directives are handled here and instructions by `assemble`
+    fn emit(&self, op: &Decoded, warnings: &mut Vec<Log>) -> Result<Vec<u8>, Log> {
+        Ok(match &op.op_type {
+            OpType::Data(size, values) => {
+                let mut vec = Vec::new();
+
+                for (idx, value) in values.iter().enumerate() {
+                    vec.extend(self.data_bytes(size, value).map_err(|e| e.in_operand(idx))?);
+                }
+
+                vec
+            }
+
+            OpType::Fill(size, count, value) => self.data_bytes(size, value).map_err(|e| e.in_operand(1))?.repeat(*count as usize),
+
+            OpType::Org(addr) => match addr.checked_sub(op.offset) {
+                Some(pad) => vec![0; pad as usize],
+                None => return Err(Log::OrgBackwards),
+            },
+
+            OpType::Define(name) => {
+                Value::Define(name.clone()).resolve_value(&self.labels, &self.defines)?;
+                vec![]
+            }
+
+            OpType::Rorg(_) | OpType::End(_) => vec![],
+            OpType::Binary(bytes) => bytes.clone(),
+            OpType::Align(_, _, fill) => vec![*fill; op.len() as usize],
+
+            _ => {
+                if op.location & 1 != 0 {
+                    return Err(Log::OddInstructionAddress);
+                }
+
+                self.assemble(op, warnings)?.into_iter().flat_map(|word| word.to_be_bytes()).collect()
+            }
+        })
+    }
+
+    /// Bytes of a single data value
+    fn data_bytes(&self, size: &DataType, value: &Value) -> Result<Vec<u8>, Log> {
+        if let Some(size) = size.float_size() {
+            let words = float::encode(value.resolve_float(&self.labels, &self.defines)?, size)?;
+            return Ok(words.into_iter().flat_map(u16::to_be_bytes).collect());
+        }
+
+        let value = value.resolve_value(&self.labels, &self.defines)?;
+
+        let number = match size {
+            DataType::Data64 => value,
+            _ => addressing::fits(value, size.len() * 8)?,
+        }.to_be_bytes();
+
+        let range = match size {
+            DataType::Data08 => 7..=7,
+            DataType::Data16 => 6..=7,
+            DataType::Data24 => 5..=7,
+            DataType::Data32 => 4..=7,
+            DataType::Data64 => 0..=7,
+            _ => unreachable!(), // floating point data is encoded above
+        };
+
+        Ok(number[range].to_vec())
+    }
+
+    /// Resolves the quick immediate in operand `operand`, which is encoded in the instruction word and must be between `min` and `max`
+    fn quick(&self, op: &Decoded, operand: usize, min: i64, max: i64) -> Result<u16, Log> {
+        let AddressingMode::DataQuick(value) = &op.operands[operand] else {
+            unreachable!()
+        };
+
+        let value = value.resolve_value(&self.labels, &self.defines).map_err(|e| e.in_operand(operand))? as i64;
+
+        match (min..=max).contains(&value) {
+            true  => Ok(value as u16),
+            false => Err(Log::QuickImmediateOutOfRange(value, min, max).in_operand(operand)),
+        }
+    }
+
+    /// Function code operand of the 68030 MMU instructions: SFC, DFC, a data register or a value from 0 to 7
+    fn function_code(&self, op: &Decoded, operand: usize) -> Result<u16, Log> {
+        match &op.operands[operand] {
+            AddressingMode::ControlReg(ControlRegister::Sfc) => Ok(0b00000),
+            AddressingMode::ControlReg(ControlRegister::Dfc) => Ok(0b00001),
+            AddressingMode::DataRegister(reg) => Ok(0b01000 | *reg as u16),
+            AddressingMode::DataQuick(_) => Ok(0b10000 | self.quick(op, operand, 0, 7)?),
+            _ => Err(Log::InvalidAddressingMode),
+        }
+    }
+
+    /// fmove and fmovem of FPCR, FPSR and FPIAR. Several can be moved at once, but only to or from memory
+    fn float_control(&self, op: &Decoded, regs: u8, to_memory: bool, ea: &AddressingMode, ea_words: (u16, Vec<u16>)) -> Result<Vec<u16>, Log> {
+        if !matches!(op.op_size, OpSize::L | OpSize::Unsized) {
+            return Err(Log::SizeOperandMismatch);
+        }
+
+        match ea {
+            AddressingMode::Immediate(OpSize::L, _) if regs.count_ones() == 1 => (),
+            AddressingMode::Immediate(_, _) => return Err(Log::SizeOperandMismatch),
+            AddressingMode::DataRegister(_) if regs.count_ones() > 1 => return Err(Log::InvalidAddressingMode),
+            AddressingMode::AddressRegister(_) if regs != 0b001 => return Err(Log::InvalidAddressingMode), // FPIAR only
+            _ => (),
+        }
+
+        let (ea, ea_ext) = ea_words;
+        let mut format = vec![op.op_type.format() | ea, (0b100 << 13) | ((to_memory as u16) << 13) | ((regs as u16) << 10)];
+        format.extend(ea_ext);
+        Ok(format)
+    }
+
+    /// ColdFire moves can't have extension words on both sides, except for a 16-bit displacement to a register indirect one
+    fn coldfire_move(&self, op: &Decoded) -> Result<(), Log> {
+        use AddressingMode::*;
+
+        let displacement_source = match op.operands[0] {
+            DataRegister(_) | AddressRegister(_) | Address(_) | AddressPostincrement(_) | AddressPredecrement(_) => return Ok(()),
+            AddressDisplacement(_, _) | PCDisplacement(_) => true,
+            _ => false,
+        };
+
+        let valid = match op.operands[1] {
+            DataRegister(_) | Address(_) | AddressPostincrement(_) | AddressPredecrement(_) => true,
+            AddressDisplacement(_, _) if displacement_source => true,
+
+            // ISA_B added byte and word immediates to a displacement
+            AddressDisplacement(_, _) => matches!(op.operands[0], Immediate(_, _)) && op.op_size != OpSize::L && !matches!(self.cpu_type, CpuType::ColdFireA),
+            _ => false,
+        };
+
+        match valid {
+            true  => Ok(()),
+            false => Err(Log::CpuTypeModeNotValid),
+        }
+    }
+
+    /// move.l between the EMAC registers, and to and from general registers. Immediates can be moved to them
+    fn mac_move(&self, op: &Decoded, ea_words: (u16, Vec<u16>), dst: u16) -> Result<Vec<u16>, Log> {
+        if op.op_size != OpSize::L {
+            return Err(Log::SizeOperandMismatch);
+        }
+
+        let (ea, ea_ext) = ea_words;
+
+        Ok(match (&op.operands[0], &op.operands[1]) {
+            (AddressingMode::Accumulator(src), AddressingMode::Accumulator(dst)) => vec![0b1010_000_100_010_000 | ((*dst as u16) << 9) | *src as u16],
+            (AddressingMode::MacControl(0b100), AddressingMode::CCR) => vec![0b1010_100_111_000_000], // from MACSR
+            (_, AddressingMode::CCR) => return Err(Log::InvalidAddressingMode),
+            (AddressingMode::Accumulator(reg) | AddressingMode::MacControl(reg), _) => vec![(0b1010_000_110 << 6) | ((*reg as u16) << 9) | (dst & 0b1111)],
+
+            (_, AddressingMode::Accumulator(reg) | AddressingMode::MacControl(reg)) => {
+                let mut format = vec![(0b1010_000_100 << 6) | ((*reg as u16) << 9) | ea];
+                format.extend(ea_ext);
+                format
+            }
+
+            _ => unreachable!(),
+        })
+    }
+
+    /// Offset or width of a bitfield, as the register flag followed by 5 bits of register or value
+    fn bitfield_part(&self, part: &BitfieldPart, min: i64, max: i64) -> Result<u16, Log> {
+        match part {
+            BitfieldPart::Register(reg) => Ok(0b100000 | *reg as u16),
+
+            BitfieldPart::Value(value) => {
+                let value = value.resolve_value(&self.labels, &self.defines)? as i64;
+
+                match (min..=max).contains(&value) {
+                    true  => Ok(value as u16 & 0b11111), // a width of 32 is stored as 0
+                    false => Err(Log::ValueOutOfRange(value, min, max)),
+                }
+            }
+        }
+    }
+
+    fn assemble(&self, op: &Decoded, warnings: &mut Vec<Log>) -> Result<Vec<u16>, Log> {
+        use OpSize::*;
+        use OpType::*;
+
+        if op.op_type.is_fpu() && !self.fpu {
+            return Err(Log::FpuDisabled);
+        }
+
+        if !op.op_type.is_supported(&self.cpu_type) {
+            return Err(Log::UnsupportedInstruction);
+        }
+
+        op.op_type.valid_size(op.op_size, &self.cpu_type)?;
+
+        op.op_type.is_valid_modes(&op.operands, op.op_size, &self.cpu_type)?;
+
+        let (ea_a1, ea_a2) = op.operands[0].effective_addressing(&self.labels, &self.defines, op.operand_location(0)).map_err(|e| e.in_operand(0))?;
+        let (ea_b1, ea_b2) = op.operands[1].effective_addressing(&self.labels, &self.defines, op.operand_location(1)).map_err(|e| e.in_operand(1))?;
+        let (ea_c1, ea_c2) = op.operands[2].effective_addressing(&self.labels, &self.defines, op.operand_location(2)).map_err(|e| e.in_operand(2))?;
+
+        Ok(match &op.op_type {
+            Branch(_) => {
+                let AddressingMode::BranchDisplacement(size, _) = op.operands[0] else {
+                    unreachable!()
+                };
+
+                match size {
+                    B => vec![op.op_type.format() | (ea_a2[0] & 0xFF)],
+                    W => vec![op.op_type.format(), ea_a2[0]],
+
+                    L => match self.cpu_type {
+                        CpuType::MC68000 | CpuType::MC68010 => return Err(Log::CpuTypeSizeNotValid),
+                        _ => vec![op.op_type.format() | 0xFF, ea_a2[0], ea_a2[1]],
+                    },
+
+                    _ => unreachable!(),
+                }
+            }
+
+            Rtd => {
+                match self.cpu_type {
+                    CpuType::MC68000 => return Err(Log::UnsupportedInstruction),
+                    _ => {
+                        vec![op.op_type.format(), ea_a2[0]]
+                    }
+                }
+            }
+
+            NoOperands(_) => vec![op.op_type.format()],
+
+            Immediates(_) => {
+                let ea = match ea_b1 {
+                    CCR_MASK => {
+                        if op.op_size == B {
+                            IMMEDIATE_MASK
+                        } else {
+                            return Err(Log::SpecialRegisterSize("CCR", 'b'));
+                        }
+                    }
+
+                    SR_MASK => {
+                        if op.op_size == W {
+                            IMMEDIATE_MASK
+                        } else {
+                            return Err(Log::SpecialRegisterSize("SR", 'w'));
+                        }
+                    }
+
+                    _ => ea_b1,
+                };
+
+                let mut format = vec![op.op_type.format() | op.op_size.size1() | ea];
+                format.extend(ea_a2);
+                format.extend(ea_b2);
+                format
+            }
+
+            AddSub(_) | OrAnd(_) => {
+                let (reg, dir, ea) = match ea_b1 & MODE_MASK == 0b000_000 {
+                    true => (ea_b1 & 0b111, 0, (ea_a1, ea_a2)),
+                    false => (ea_a1 & 0b111, 1 << 8, (ea_b1, ea_b2)),
+                };
+
+                let mut format = vec![op.op_type.format() | (reg << 9) | dir | op.op_size.size1() | ea.0];
+                format.extend(ea.1);
+                format
+            }
+
+            Eor => {
+                let reg = ea_a1 & 0b111;
+                let mut format = vec![op.op_type.format() | (reg << 9) | op.op_size.size1() | ea_b1];
+                format.extend(ea_b2);
+                format
+            }
+
+            AddSubA(_) => {
+                let reg = (ea_b1 & 0b111) << 9;
+
+                let opmode = match op.op_size {
+                    OpSize::W => 0b011 << 6,
+                    OpSize::L => 0b111 << 6,
+                    _ => unreachable!(),
+                };
+
+                let mut format = vec![op.op_type.format() | reg | opmode | ea_a1];
+                format.extend(ea_a2);
+                format
+            }
+
+            AddSubX(_) | Bcd(_) => {
+                let rm = match ea_a1 & MODE_MASK == 0b000_000 {
+                    true => 0,
+                    false => 1 << 3,
+                };
+
+                let rx = (ea_a1 & 0b111) << 9;
+                let ry = ea_b1 & 0b111;
+
+                vec![op.op_type.format() | rx | (1 << 8) | rm | ry]
+            }
+
+            AddSubQ(_) => {
+                let imm = self.quick(op, 0, 1, 8)?;
+
+                if ea_b1 & MODE_MASK == ADDRESS_REGISTER_MASK && op.op_size == OpSize::W {
+                    warnings.push(Log::AddSubQAddressWord);
+                }
+
+                let mut format = vec![op.op_type.format() | ((imm & 0b111) << 9) | op.op_size.size1() | ea_b1];
+                format.extend(ea_b2);
+                format
+            }
+
+            Jump(_) | Tas | Pea | Scc(_) | Nbcd => {
+                let mut format = vec![op.op_type.format() | ea_a1];
+                format.extend(ea_a2);
+                format
+            }
+
+            Move if matches!(op.operands[0], AddressingMode::Accumulator(_) | AddressingMode::MacControl(_)) ||
+                    matches!(op.operands[1], AddressingMode::Accumulator(_) | AddressingMode::MacControl(_)) => {
+                self.mac_move(op, (ea_a1, ea_a2), ea_b1)?
             }
 
             Move => {
@@ -428,262 +1558,735 @@ impl Assembler {
                                     format.extend(ea_b2);
                                     format
                                 } else {
-                                    todo!("error")
+                                    return Err(Log::SpecialRegisterSize("CCR", 'w'));
+                                }
+                            }
+                        }
+                    }
+
+                    SR_MASK => {
+                        if op.op_size == W {
+                            let mut format = vec![(0b0100_0000_11 << 6) | ea_b1];
+                            format.extend(ea_b2);
+                            format
+                        } else {
+                            return Err(Log::SpecialRegisterSize("SR", 'w'));
+                        }
+                    }
+
+                    USP_MASK => match op.op_size == L {
+                        true if ea_b1 & MODE_MASK != ADDRESS_REGISTER_MASK => return Err(Log::InvalidAddressingMode),
+                        true  => vec![(0b0100_1110_0110 << 4) | ea_b1],
+                        false => return Err(Log::SpecialRegisterSize("USP", 'l')),
+                    }
+
+                    _ => {
+                        match ea_b1 {
+                            CCR_MASK => {
+                                if op.op_size == W {
+                                    let mut format = vec![(0b0100_0100_11 << 6) | ea_a1];
+                                    format.extend(ea_a2);
+                                    format
+                                } else {
+                                    return Err(Log::SpecialRegisterSize("CCR", 'w'));
+                                }
+                            }
+
+                            SR_MASK => {
+                                if op.op_size == W {
+                                    let mut format = vec![(0b0100_0110_11 << 6) | ea_a1];
+                                    format.extend(ea_a2);
+                                    format
+                                } else {
+                                    return Err(Log::SpecialRegisterSize("SR", 'w'));
+                                }
+                            }
+
+                            USP_MASK => {
+                                if ea_a1 & MODE_MASK != ADDRESS_REGISTER_MASK {
+                                    return Err(Log::InvalidAddressingMode);
+                                }
+
+                                if op.op_size == L {
+                                    let reg = ea_a1 & 0b111;
+                                    vec![(0b0100_1110_0110 << 4) | reg]
+                                } else {
+                                    return Err(Log::SpecialRegisterSize("USP", 'l'));
+                                }
+                            }
+
+                            _ => { //normal move
+                                if self.cpu_type.is_coldfire() {
+                                    self.coldfire_move(op)?;
                                 }
+
+                                let ea_dst_reorder = ((ea_b1 & 0b000_111) << 9) | ((ea_b1 & 0b111_000) << 3);
+                                let mut format = vec![op.op_size.size_move() | ea_dst_reorder | ea_a1];
+                                format.extend(ea_a2);
+                                format.extend(ea_b2);
+                                format
                             }
                         }
                     }
+                }
+            }
+
+            MoveA => {
+                let reg = (ea_b1 & 0b111) << 9;
+                let mut format = vec![op.op_size.size_move() | reg | op.op_type.format() | ea_a1];
+                format.extend(ea_a2);
+                format
+            }
+
+            Movec => {
+                match self.cpu_type {
+                    CpuType::MC68000 => return Err(Log::UnsupportedInstruction),
+                    _ => {
+                        let (dr, rn, cr) = match &op.operands[0] {
+                            AddressingMode::ControlReg(cr) => (0, 1, cr.format(&self.cpu_type)?),
+                            AddressingMode::USP => (0, 1, ControlRegister::Usp.format(&self.cpu_type)?),
+                            
+                            _ => {
+                                let cr = match &op.operands[1] {
+                                    AddressingMode::ControlReg(cr) => cr,
+                                    AddressingMode::USP => &ControlRegister::Usp,
+                                    _ => unreachable!(),
+                                };
+        
+                                (1, 0, cr.format(&self.cpu_type)?)
+                            }
+                        };
+        
+                        let (ad, reg) = match op.operands[rn] {
+                            AddressingMode::DataRegister(r) => (0 << 15, (r as u16) << 12),
+                            AddressingMode::AddressRegister(r) => (1 << 15, (r as u16) << 12),
+                            _ => unreachable!(),
+                        };
+        
+                        vec![
+                            op.op_type.format() | dr,
+                            ad | reg | cr,
+                        ]
+                    }
+                }
+            }
+
+            Moves => {
+                match self.cpu_type {
+                    CpuType::MC68000 => return Err(Log::UnsupportedInstruction),
+                    _ => {
+                        //todo: use op.operands instead!
+                        let (ad, reg, dr) = if ea_a1 & MODE_MASK == DATA_REGISTER_MASK {
+                            (0 << 15, (ea_a1 & 111) << 12, 1 << 11)
+                        } else if ea_a1 & MODE_MASK == ADDRESS_REGISTER_MASK {
+                            (1 << 15, (ea_a1 & 111) << 12, 1 << 11)
+                        } else if ea_b1 & MODE_MASK == DATA_REGISTER_MASK {
+                            (0 << 15, (ea_b1 & 111) << 12, 0 << 11)
+                        } else { //ea_b1 & MODE_MASK == ADDRESS_REGISTER_MASK
+                            (1 << 15, (ea_b1 & 111) << 12, 0 << 11)
+                        };
+        
+                        let (ea, ea2) = match dr == 0 {
+                            true  => (ea_a1, ea_a2),
+                            false => (ea_b1, ea_b2),
+                        };
+        
+                        let mut format = vec![
+                            op.op_type.format() | op.op_size.size1() | ea,
+                            ad | reg | dr,
+                        ];
+                        format.extend(ea2);
+                        format
+                    }
+                }
+            }
 
-                    SR_MASK => {
-                        if op.op_size == W {
-                            let mut format = vec![(0b0100_0000_11 << 6) | ea_b1];
-                            format.extend(ea_b2);
-                            format
-                        } else {
-                            todo!("error")
+            BitManip(_) => {
+                if op.op_size == B && ea_b1 & MODE_MASK == DATA_REGISTER_MASK || op.op_size == L && ea_b1 & MODE_MASK != DATA_REGISTER_MASK {
+                    return Err(Log::SizeOperandMismatch);
+                }
+
+                let op1 = match ea_a1 & MODE_MASK == DATA_REGISTER_MASK {
+                    true => (((ea_a1 & 0b111) << 9) | (1 << 8), vec![]),
+
+                    false => {
+                        //todo: rename asd and document what this does
+                        let asd = match ea_b1 & MODE_MASK == DATA_REGISTER_MASK {
+                            true  => 1,
+                            false => 0,
+                        };
+
+                        // the bit number wraps around, at 32 for data registers and 8 for memory
+                        let max = match ea_b1 & MODE_MASK == DATA_REGISTER_MASK {
+                            true  => 31,
+                            false => 7,
+                        };
+
+                        if ea_a2[asd] > max {
+                            warnings.push(Log::BitNumberWraps(ea_a2[asd] as i64, max as i64));
                         }
+
+                        ((1 << 11), vec![ea_a2[asd] & 0xFF])
                     }
+                };
 
-                    USP_MASK => match op.op_size == L {
-                        true  => vec![(0b0100_11100110) | ea_b1],
-                        false => todo!("error"),
+                let mut format = vec![op.op_type.format() | op1.0 | ea_b1];
+                format.extend(op1.1);
+                format.extend(ea_b2);
+                format
+            }
+
+            Misc1(_) | Tst => {
+                if let (Tst, CpuType::MC68000 | CpuType::MC68010) = (&op.op_type, &self.cpu_type) {
+                    if ea_a1 & MODE_MASK == ADDRESS_REGISTER_MASK || ea_a1 == IMMEDIATE_MASK || ea_a1 == 0b111_010 || ea_a1 == 0b111_011 {
+                        return Err(Log::CpuTypeModeNotValid);
                     }
+                }
 
-                    _ => {
-                        match ea_b1 {
-                            CCR_MASK => {
-                                if op.op_size == W {
-                                    let mut format = vec![(0b0100_0100_11 << 6) | ea_a1];
-                                    format.extend(ea_a2);
-                                    format
-                                } else {
-                                    todo!("error")
-                                }
-                            }
+                let mut format = vec![op.op_type.format() | op.op_size.size1() | ea_a1];
+                format.extend(ea_a2);
+                format
+            }
 
-                            SR_MASK => {
-                                if op.op_size == W {
-                                    let mut format = vec![(0b0100_0110_11 << 6) | ea_a1];
-                                    format.extend(ea_a2);
-                                    format
-                                } else {
-                                    todo!("error")
-                                }
-                            }
+            MoveQ => {
+                let data = self.quick(op, 0, -128, 127)? & 0xFF;
+                let reg = (ea_b1 & 0b111) << 9;
+                vec![op.op_type.format() | reg | data]
+            }
 
-                            USP_MASK => {
-                                if op.op_size == L {
-                                    let reg = ea_a1 & 0b111;
-                                    vec![(0b0100_11100110) | reg]
-                                } else {
-                                    todo!("error")
-                                }
-                            }
+            Rotation(rot_type, dir) => {
+                let mode = match ea_b1 & MODE_MASK == 0b000_000 {
+                    true => {
+                        let (count_reg, ir) = match ea_a1 & MODE_MASK == 0b000_000 {
+                            true => (ea_a1 & 0b111, true),
+                            false => (self.quick(op, 0, 1, 8)? & 0b111, false),
+                        };
 
-                            _ => { //normal move
-                                let ea_dst_reorder = ((ea_b1 & 0b000_111) << 9) | ((ea_b1 & 0b111_000) << 3);
-                                let mut format = vec![op.op_size.size_move() | ea_dst_reorder | ea_a1];
-                                format.extend(ea_a2);
-                                format.extend(ea_b2);
-                                format
-                            }
+                        let mut bits = (ea_b1 & 0b111) | ((*rot_type as u16) << 3);
+                        bits |= op.op_size.size1();
+                        bits |= count_reg << 9;
+                        bits |= (ir as u16) << 5;
+                        (bits, vec![])
+                    }
+
+                    false => {
+                        let mut bits = ea_a1;
+                        bits |= 0b11 << 6;
+                        bits |= (*rot_type as u16) << 9;
+
+                        (bits, ea_a2)
+                    }
+                };
+
+                let mut format = vec![op.op_type.format() | ((*dir as u16) << 8) | mode.0];
+                format.extend(mode.1);
+                format
+            }
+
+            MulDiv(format) if op.op_size == L => {
+                if matches!(self.cpu_type, CpuType::MC68000 | CpuType::MC68010) {
+                    return Err(Log::CpuTypeSizeNotValid);
+                }
+
+                let mul = format & (0b0100 << 12) != 0;
+                let signed = format & (1 << 8) != 0;
+
+                // Dh:Dl for a 64-bit product or dividend. For 32-bit division, the remainder register is the quotient register
+                let (low, high, long) = match op.operands[1] {
+                    AddressingMode::RegisterPair(high, low) => (low, high, true),
+                    AddressingMode::DataRegister(reg) if mul => (reg, 0, false),
+                    AddressingMode::DataRegister(reg) => (reg, reg, false),
+                    _ => unreachable!(),
+                };
+
+                let opcode = match mul {
+                    true  => 0b0100_1100_00 << 6,
+                    false => 0b0100_1100_01 << 6,
+                };
+
+                let mut format = vec![opcode | ea_a1, ((low as u16) << 12) | ((signed as u16) << 11) | ((long as u16) << 10) | high as u16];
+                format.extend(ea_a2);
+                format
+            }
+
+            DivLong(signed) | Rem(signed) => {
+                let AddressingMode::RegisterPair(remainder, quotient) = op.operands[1] else {
+                    unreachable!()
+                };
+
+                let mut format = vec![op.op_type.format() | ea_a1, ((quotient as u16) << 12) | ((*signed as u16) << 11) | remainder as u16];
+                format.extend(ea_a2);
+                format
+            }
+
+            Lea | Chk | MulDiv(_) => {
+                // register pairs only exist for the long forms
+                if let AddressingMode::RegisterPair(_, _) = op.operands[1] {
+                    return Err(Log::InvalidAddressingMode);
+                }
+
+                let reg = (ea_b1 & 0b111) << 9;
+                let mut format = vec![op.op_type.format() | reg | ea_a1];
+                format.extend(ea_a2);
+                format
+            }
+
+            Exg => {
+                let (mode, ry) = match ea_b1 & MODE_MASK == 0b000_000 {
+                    true => (0b01000 << 3, (ea_b1 & 0b111)),
+
+                    false => {
+                        let op_mode = match ea_a1 & MODE_MASK == 0b000_000 {
+                            true => 0b10001 << 3,
+                            false => 0b01001 << 3,
+                        };
+
+                        (op_mode, ea_a1 & 0b111)
+                    }
+                };
+
+                let rx = (ea_a1 & 0b111) << 9;
+
+                vec![op.op_type.format() | rx | mode | ry]
+            }
+
+            Ext => {
+                let reg = ea_a1 & 0b111;
+                let size = op.op_size.size2();
+                vec![op.op_type.format() | size | reg]
+            }
+
+            Swap | Unlk => {
+                let reg = ea_a1 & 0b111;
+                vec![op.op_type.format() | reg]
+            }
+
+            Link => {
+                let reg = ea_a1 & 0b111;
+
+                let opcode = match op.op_size {
+                    L if matches!(self.cpu_type, CpuType::MC68000 | CpuType::MC68010) => return Err(Log::CpuTypeSizeNotValid),
+                    L => 0b0100_1000_0000_1 << 3,
+                    _ => op.op_type.format(),
+                };
+
+                let mut format = vec![opcode | reg];
+                format.extend(ea_b2);
+                format
+            }
+
+            Extb | Bitrev(_) | Sats => vec![op.op_type.format() | (ea_a1 & 0b111)],
+
+            Mov3q => {
+                // -1 is stored as 0
+                let data = match self.quick(op, 0, -1, 7)? {
+                    0 => return Err(Log::QuickImmediateOutOfRange(0, -1, 7).in_operand(0)),
+                    0xFFFF => 0,
+                    data => data,
+                };
+
+                let mut format = vec![op.op_type.format() | (data << 9) | ea_b1];
+                format.extend(ea_b2);
+                format
+            }
+
+            Mvx(_) => {
+                let mut format = vec![op.op_type.format() | ((ea_b1 & 0b111) << 9) | (((op.op_size == W) as u16) << 6) | ea_a1];
+                format.extend(ea_a2);
+                format
+            }
+
+            Mac(subtract) => {
+                let AddressingMode::MacPair(ry, rx, scale) = op.operands[0] else {
+                    unreachable!()
+                };
+
+                let (ry, rx) = (ry as u16, rx as u16);
+
+                // the upper or lower word is only picked for word sized operands
+                if op.op_size == L && (ry | rx) & (1 << 4) != 0 {
+                    return Err(Log::SizeOperandMismatch);
+                }
+
+                let ext = (((op.op_size == L) as u16) << 11) | ((scale as u16) << 9) | ((*subtract as u16) << 8) | ((rx >> 4) << 7) | ((ry >> 4) << 6);
+
+                match op.operands[2] {
+                    AddressingMode::Accumulator(acc) => {
+                        let acc = acc as u16;
+                        vec![op.op_type.format() | ((rx & 0b111) << 9) | ((acc & 1) << 7) | (((rx >> 3) & 1) << 6) | (ry & 0b1111), ext | ((acc >> 1) << 4)]
+                    }
+
+                    // the low bit of the accumulator is inverted, which keeps ACC0 at the encoding of the original MAC unit
+                    AddressingMode::MacLoad(rw, acc, mask) => {
+                        let (rw, acc) = (rw as u16, acc as u16);
+                        let mut format = vec![
+                            op.op_type.format() | ((rw & 0b111) << 9) | ((!acc & 1) << 7) | ((rw >> 3) << 6) | ea_b1,
+                            ext | ((rx & 0b1111) << 12) | ((mask as u16) << 5) | ((acc >> 1) << 4) | (ry & 0b1111),
+                        ];
+
+                        format.extend(ea_b2);
+                        format
+                    }
+
+                    _ => unreachable!(),
+                }
+            }
+
+            Movclr => {
+                let AddressingMode::Accumulator(acc) = op.operands[0] else {
+                    unreachable!()
+                };
+
+                vec![op.op_type.format() | ((acc as u16) << 9) | (ea_b1 & 0b1111)]
+            }
+
+            FOp(_) | FSincos => {
+                // sincos puts the cosine in FPc and the sine in FPs
+                let (opmode, dst) = match (&op.op_type, &op.operands[1]) {
+                    (FSincos, AddressingMode::FloatPair(c, s)) => (0x30 | *c as u16, Some(*s)),
+                    (FOp(opmode), AddressingMode::FloatRegister(dst)) => (*opmode as u16, Some(*dst)),
+                    (FOp(0x3A), _) => (0x3A, Some(0)), // ftst
+                    (FOp(opmode), _) => (*opmode as u16, None),
+                    _ => unreachable!(),
+                };
+
+                match op.operands[0] {
+                    AddressingMode::FloatRegister(src) => {
+                        if !matches!(op.op_size, X | Unsized) {
+                            return Err(Log::SizeOperandMismatch);
                         }
+
+                        vec![op.op_type.format(), ((src as u16) << 10) | ((dst.unwrap_or(src) as u16) << 7) | opmode]
                     }
+
+                    _ => {
+                        let mut format = vec![op.op_type.format() | ea_a1, (0b010 << 13) | op.float_format(&op.operands[0])? | ((dst.unwrap_or(0) as u16) << 7) | opmode];
+                        format.extend(ea_a2);
+                        format
+                    }
+                }
+            }
+
+            FMove => match (&op.operands[0], &op.operands[1]) {
+                (AddressingMode::FloatControl(regs), ea) => self.float_control(op, *regs, true, ea, (ea_b1, ea_b2))?,
+                (ea, AddressingMode::FloatControl(regs)) => self.float_control(op, *regs, false, ea, (ea_a1, ea_a2))?,
+
+                (AddressingMode::FloatRegister(src), AddressingMode::FloatRegister(dst)) => {
+                    if !matches!(op.op_size, X | Unsized) {
+                        return Err(Log::SizeOperandMismatch);
+                    }
+
+                    vec![op.op_type.format(), ((*src as u16) << 10) | ((*dst as u16) << 7)]
+                }
+
+                (AddressingMode::FloatRegister(src), ea) => {
+                    // packed decimal is written with a k-factor of 17 significant digits
+                    let k_factor = match op.op_size {
+                        P => 17,
+                        _ => 0,
+                    };
+
+                    let mut format = vec![op.op_type.format() | ea_b1, (0b011 << 13) | op.float_format(ea)? | ((*src as u16) << 7) | k_factor];
+                    format.extend(ea_b2);
+                    format
+                }
+
+                (ea, AddressingMode::FloatRegister(dst)) => {
+                    let mut format = vec![op.op_type.format() | ea_a1, (0b010 << 13) | op.float_format(ea)? | ((*dst as u16) << 7)];
+                    format.extend(ea_a2);
+                    format
                 }
+
+                _ => unreachable!(),
+            },
+
+            FMovem => {
+                let (list, to_memory, (ea, ea_ext), ea_mode) = match (&op.operands[0], &op.operands[1]) {
+                    (AddressingMode::FloatControl(regs), ea) => return self.float_control(op, *regs, true, ea, (ea_b1, ea_b2)),
+                    (ea, AddressingMode::FloatControl(regs)) => return self.float_control(op, *regs, false, ea, (ea_a1, ea_a2)),
+                    (AddressingMode::FloatRegister(_) | AddressingMode::FloatRegisterList(_) | AddressingMode::DataRegister(_), ea) => (&op.operands[0], true, (ea_b1, ea_b2), ea),
+                    (ea, list) => (list, false, (ea_a1, ea_a2), ea),
+                };
+
+                if !matches!(op.op_size, X | Unsized) {
+                    return Err(Log::SizeOperandMismatch);
+                }
+
+                // with predecrement FP0 is bit 0, otherwise it's bit 7
+                let predecrement = matches!(ea_mode, AddressingMode::AddressPredecrement(_));
+
+                let (dynamic, list) = match list {
+                    AddressingMode::DataRegister(reg) => (1, (*reg as u16) << 4),
+                    AddressingMode::FloatRegister(reg) if predecrement => (0, 1 << reg),
+                    AddressingMode::FloatRegisterList(mask) if predecrement => (0, *mask as u16),
+                    AddressingMode::FloatRegister(reg) => (0, (1u8 << reg).reverse_bits() as u16),
+                    AddressingMode::FloatRegisterList(mask) => (0, mask.reverse_bits() as u16),
+                    _ => unreachable!(),
+                };
+
+                let mode = ((!predecrement as u16) << 1) | dynamic;
+
+                let mut format = vec![op.op_type.format() | ea, (0b11 << 14) | ((to_memory as u16) << 13) | (mode << 11) | list];
+                format.extend(ea_ext);
+                format
+            }
+
+            FMovecr => {
+                if !matches!(op.op_size, X | Unsized) {
+                    return Err(Log::SizeOperandMismatch);
+                }
+
+                let AddressingMode::FloatRegister(dst) = op.operands[1] else {
+                    unreachable!()
+                };
+
+                vec![op.op_type.format(), (0b010111 << 10) | ((dst as u16) << 7) | self.quick(op, 0, 0, 0x7F)?]
+            }
+
+            FBranch(_) => {
+                let AddressingMode::BranchDisplacement(size, _) = op.operands[0] else {
+                    unreachable!()
+                };
+
+                match size {
+                    W => vec![op.op_type.format(), ea_a2[0]],
+                    _ => vec![op.op_type.format() | (1 << 6), ea_a2[0], ea_a2[1]],
+                }
+            }
+
+            FScc(cond) => {
+                let mut format = vec![op.op_type.format() | ea_a1, *cond as u16];
+                format.extend(ea_a2);
+                format
+            }
+
+            FDbcc(cond) => vec![op.op_type.format() | (ea_a1 & 0b111), *cond as u16, ea_b2[0]],
+
+            FTrapcc(cond) => {
+                let opmode = match (op.op_size, &op.operands[0]) {
+                    (Unsized, AddressingMode::Empty) => 0b100,
+                    (_, AddressingMode::Empty) => return Err(Log::SizeOperandMismatch),
+                    (L, _) => 0b011,
+                    _ => 0b010,
+                };
+
+                let mut format = vec![op.op_type.format() & !0b111 | opmode, *cond as u16];
+                format.extend(ea_a2);
+                format
             }
 
-            MoveA => {
-                let reg = (ea_b1 & 0b111) << 9;
-                let mut format = vec![op.op_size.size_move() | reg | op.op_type.format() | ea_a1];
+            FNop => vec![op.op_type.format(), 0],
+
+            FSave | FRestore => {
+                let mut format = vec![op.op_type.format() | ea_a1];
                 format.extend(ea_a2);
                 format
             }
 
-            Movec => {
-                match self.cpu_type {
-                    CpuType::MC68000 => return Err(Log::UnsupportedInstruction),
-                    _ => {
-                        let (dr, rn, cr) = match &op.operands[0] {
-                            AddressingMode::ControlReg(cr) => (0, 1, cr.format()),
-                            AddressingMode::USP => (0, 1, ControlRegister::Usp.format()),
-                            
-                            _ => {
-                                let cr = match &op.operands[1] {
-                                    AddressingMode::ControlReg(cr) => cr,
-                                    AddressingMode::USP => &ControlRegister::Usp,
-                                    _ => unreachable!(),
-                                };
-        
-                                (1, 0, cr.format())
-                            }
-                        };
-        
-                        let (ad, reg) = match op.operands[rn] {
-                            AddressingMode::DataRegister(r) => (0 << 15, (r as u16) << 12),
-                            AddressingMode::AddressRegister(r) => (1 << 15, (r as u16) << 12),
-                            _ => unreachable!(),
-                        };
-        
-                        vec![
-                            op.op_type.format() | dr,
-                            ad | reg | cr,
-                        ]
-                    }
-                }
+            PFlush030(all) => {
+                let (ext, ea) = match (all, &op.operands[2]) {
+                    (true, _) => (0b001_001 << 10, 0),
+                    (false, AddressingMode::Empty) => ((0b001_100 << 10) | (self.quick(op, 1, 0, 7)? << 5) | self.function_code(op, 0)?, 0),
+                    (false, _) => ((0b001_110 << 10) | (self.quick(op, 1, 0, 7)? << 5) | self.function_code(op, 0)?, ea_c1),
+                };
+
+                let mut format = vec![op.op_type.format() | ea, ext];
+                format.extend(ea_c2);
+                format
             }
 
-            Moves => {
-                match self.cpu_type {
-                    CpuType::MC68000 => return Err(Log::UnsupportedInstruction),
-                    _ => {
-                        //todo: use op.operands instead!
-                        let (ad, reg, dr) = if ea_a1 & MODE_MASK == DATA_REGISTER_MASK {
-                            (0 << 15, (ea_a1 & 111) << 12, 1 << 11)
-                        } else if ea_a1 & MODE_MASK == ADDRESS_REGISTER_MASK {
-                            (1 << 15, (ea_a1 & 111) << 12, 1 << 11)
-                        } else if ea_b1 & MODE_MASK == DATA_REGISTER_MASK {
-                            (0 << 15, (ea_b1 & 111) << 12, 0 << 11)
-                        } else { //ea_b1 & MODE_MASK == ADDRESS_REGISTER_MASK
-                            (1 << 15, (ea_b1 & 111) << 12, 0 << 11)
-                        };
-        
-                        let (ea, ea2) = match dr == 0 {
-                            true  => (ea_a1, ea_a2),
-                            false => (ea_b1, ea_b2),
-                        };
-        
-                        let mut format = vec![
-                            op.op_type.format() | op.op_size.size1() | ea,
-                            ad | reg | dr,
-                        ];
-                        format.extend(ea2);
-                        format
-                    }
-                }
+            PLoad(read) => {
+                let mut format = vec![op.op_type.format() | ea_b1, (0b001_000 << 10) | ((*read as u16) << 9) | self.function_code(op, 0)?];
+                format.extend(ea_b2);
+                format
             }
 
-            BitManip(_) => {
-                if op.op_size == B && ea_b1 & MODE_MASK == DATA_REGISTER_MASK || op.op_size == L && ea_b1 & MODE_MASK != DATA_REGISTER_MASK {
+            PTest030(read) => {
+                let level = self.quick(op, 2, 0, 7)?;
+
+                let mut format = vec![op.op_type.format() | ea_b1, (0b100 << 13) | (level << 10) | ((*read as u16) << 9) | self.function_code(op, 0)?];
+                format.extend(ea_b2);
+                format
+            }
+
+            PMove(flush_disable) => {
+                let (cr, ea, ea_ext, to_memory) = match (&op.operands[0], &op.operands[1]) {
+                    (AddressingMode::ControlReg(cr), _) => (cr, ea_b1, ea_b2, true),
+                    (_, AddressingMode::ControlReg(cr)) => (cr, ea_a1, ea_a2, false),
+                    _ => unreachable!(),
+                };
+
+                let Some((reg, size)) = cr.pmmu_format() else {
+                    return Err(Log::UnsupportedControlRegister);
+                };
+
+                if op.op_size != Unsized && op.op_size != size {
                     return Err(Log::SizeOperandMismatch);
                 }
 
-                let op1 = match ea_a1 & MODE_MASK == DATA_REGISTER_MASK {
-                    true => (((ea_a1 & 0b111) << 9) | (1 << 8), vec![]),
+                // only writes to the translation registers can disable flushing
+                if *flush_disable && (to_memory || size == W) {
+                    return Err(Log::InvalidAddressingMode);
+                }
 
-                    false => {
-                        //todo: rename asd and document what this does
-                        let asd = match ea_b1 & MODE_MASK == DATA_REGISTER_MASK {
-                            true  => 1,
-                            false => 0,
-                        };
+                let mut format = vec![op.op_type.format() | ea, reg | ((to_memory as u16) << 9) | ((*flush_disable as u16) << 8)];
+                format.extend(ea_ext);
+                format
+            }
 
-                        ((1 << 11), vec![(ea_a2[asd] & 0xFF) as u16])
-                    }
+            // pflusha and pflushan have no operand
+            PFlush(_) | PTest(_) => match op.operands[0] {
+                AddressingMode::Address(reg) => vec![op.op_type.format() | reg as u16],
+                _ => vec![op.op_type.format()],
+            },
+
+            Cache(_) => {
+                let AddressingMode::Caches(caches) = op.operands[0] else {
+                    unreachable!()
                 };
 
-                let mut format = vec![op.op_type.format() | op1.0 | ea_b1];
-                format.extend(op1.1);
-                format.extend(ea_b2);
-                format
+                let reg = match op.operands[1] {
+                    AddressingMode::Address(reg) => reg as u16,
+                    _ => 0,
+                };
+
+                vec![op.op_type.format() | ((caches as u16) << 6) | reg]
             }
 
-            Misc1(_) | Tst => {
-                match (&op.op_type, &self.cpu_type) {
-                    (Tst, CpuType::MC68000 | CpuType::MC68010) => {
-                        if ea_a1 & MODE_MASK == ADDRESS_REGISTER_MASK || ea_a1 == IMMEDIATE_MASK || ea_a1 == 0b111_010 || ea_a1 == 0b111_011 {
-                            return Err(Log::CpuTypeModeNotValid);
-                        }
+            Move16 => {
+                use AddressingMode::{AbsoluteLong, Address, AddressPostincrement};
+
+                let (opmode, reg, ea_ext) = match (&op.operands[0], &op.operands[1]) {
+                    (AddressPostincrement(x), AddressPostincrement(y)) => {
+                        return Ok(vec![op.op_type.format() | (0b100 << 3) | *x as u16, (1 << 15) | ((*y as u16) << 12)]);
                     }
 
-                    _ => (),
-                }
+                    (AddressPostincrement(reg), AbsoluteLong(_)) => (0b00, reg, ea_b2),
+                    (AbsoluteLong(_), AddressPostincrement(reg)) => (0b01, reg, ea_a2),
+                    (Address(reg), AbsoluteLong(_)) => (0b10, reg, ea_b2),
+                    (AbsoluteLong(_), Address(reg)) => (0b11, reg, ea_a2),
+                    _ => return Err(Log::InvalidAddressingMode),
+                };
 
-                let mut format = vec![op.op_type.format() | op.op_size.size1() | ea_a1];
-                format.extend(ea_a2);
+                let mut format = vec![op.op_type.format() | (opmode << 3) | *reg as u16];
+                format.extend(ea_ext);
                 format
             }
 
-            MoveQ => {
-                let data = ea_a2[0] & 0b111;
-                let reg = (ea_b1 & 0b111) << 9;
-                vec![op.op_type.format() | reg | data]
+            Lpstop => {
+                let mut format = vec![op.op_type.format(), 0b0000_0001_1100_0000];
+                format.extend(ea_a2);
+                format
             }
 
-            Rotation(rot_type, dir) => {
-                let mode = match ea_b1 & MODE_MASK == 0b000_000 {
-                    true => {
-                        let (count_reg, ir) = match ea_a1 & MODE_MASK == 0b000_000 {
-                            true => (ea_a1 & 0b111, true),
-                            false => (ea_a2[0] & 0b111, false),
-                        };
+            Tbl(signed, no_rounding) => {
+                let ext = ((ea_b1 & 0b111) << 12) | ((*signed as u16) << 11) | ((*no_rounding as u16) << 10) | op.op_size.size1();
 
-                        let mut bits = (ea_b1 & 0b111) | ((*rot_type as u16) << 3);
-                        bits |= op.op_size.size1();
-                        bits |= (count_reg as u16) << 9;
-                        bits |= (ir as u16) << 5;
-                        (bits, vec![])
+                match op.operands[0] {
+                    // interpolation between two data registers
+                    AddressingMode::RegisterPair(ym, yn) => vec![op.op_type.format() | ym as u16, ext | yn as u16],
+
+                    _ => {
+                        let mut format = vec![op.op_type.format() | ea_a1, ext | (1 << 8)];
+                        format.extend(ea_a2);
+                        format
                     }
+                }
+            }
 
-                    false => {
-                        let mut bits = ea_a1;
-                        bits |= 0b11 << 6;
-                        bits |= (*rot_type as u16) << 9;
+            Bitfield(format) => {
+                // bfins takes the register first, bfextu, bfexts and bfffo last, and the others have none
+                let (reg, ea, ea_ext, field) = match format {
+                    0b111 => (ea_a1 & 0b111, ea_b1, ea_b2, &op.operands[2]),
+                    0b000 | 0b010 | 0b100 | 0b110 => (0, ea_a1, ea_a2, &op.operands[1]),
+                    _ => (ea_b1 & 0b111, ea_a1, ea_a2, &op.operands[2]),
+                };
 
-                        (bits, ea_a2)
-                    }
+                let AddressingMode::Bitfield(offset, width) = field else {
+                    unreachable!()
                 };
 
-                let mut format = vec![op.op_type.format() | ((*dir as u16) << 8) | mode.0];
-                format.extend(mode.1);
+                let offset = self.bitfield_part(offset, 0, 31)?;
+                let width = self.bitfield_part(width, 1, 32)?;
+
+                let mut format = vec![op.op_type.format() | ea, (reg << 12) | (offset << 6) | width];
+                format.extend(ea_ext);
                 format
             }
 
-            Lea | Chk | MulDiv(_) => {
-                let reg = (ea_b1 & 0b111) << 9;
-                let mut format = vec![op.op_type.format() | reg | ea_a1];
-                format.extend(ea_a2);
+            Cas => {
+                let size = match op.op_size {
+                    B => 0b01 << 9,
+                    W => 0b10 << 9,
+                    _ => 0b11 << 9,
+                };
+
+                let mut format = vec![op.op_type.format() | size | ea_c1, ((ea_b1 & 0b111) << 6) | (ea_a1 & 0b111)];
+                format.extend(ea_c2);
                 format
             }
 
-            Exg => {
-                let (mode, ry) = match ea_b1 & MODE_MASK == 0b000_000 {
-                    true => (0b01000 << 3, (ea_b1 & 0b111)),
-
-                    false => {
-                        let op_mode = match ea_a1 & MODE_MASK == 0b000_000 {
-                            true => 0b10001 << 3,
-                            false => 0b01001 << 3,
-                        };
+            Cas2 => {
+                let (AddressingMode::RegisterPair(dc1, dc2), AddressingMode::RegisterPair(du1, du2), AddressingMode::IndirectPair(rn1, rn2)) = (&op.operands[0], &op.operands[1], &op.operands[2]) else {
+                    unreachable!()
+                };
 
-                        (op_mode, ea_a1 & 0b111)
-                    }
+                let size = match op.op_size {
+                    W => 0b10 << 9,
+                    _ => 0b11 << 9,
                 };
 
-                let rx = (ea_a1 & 0b111) << 9;
+                vec![
+                    op.op_type.format() | size,
+                    ((*rn1 as u16) << 12) | ((*du1 as u16) << 6) | *dc1 as u16,
+                    ((*rn2 as u16) << 12) | ((*du2 as u16) << 6) | *dc2 as u16,
+                ]
+            }
 
-                vec![op.op_type.format() | rx | mode | ry]
+            Cmp2(chk) => {
+                // the register is 0-7 for data registers and 8-15 for address registers, same as the mode and register bits
+                let reg = ea_b1 & 0b1111;
+
+                let mut format = vec![op.op_type.format() | (op.op_size.size1() << 3) | ea_a1, (reg << 12) | ((*chk as u16) << 11)];
+                format.extend(ea_a2);
+                format
             }
 
-            Ext => {
-                let reg = ea_a1 & 0b111;
-                let size = op.op_size.size2();
-                vec![op.op_type.format() | size | reg]
+            Pack(_) => {
+                let rm = match op.operands[0] {
+                    AddressingMode::DataRegister(_) => 0,
+                    _ => 1 << 3,
+                };
+
+                let mut format = vec![op.op_type.format() | ((ea_b1 & 0b111) << 9) | rm | (ea_a1 & 0b111)];
+                format.extend(ea_c2);
+                format
             }
 
-            Swap | Unlk => {
-                let reg = ea_a1 & 0b111;
-                vec![op.op_type.format() | reg]
+            TrapCc(_) => {
+                let opmode = match (op.op_size, &op.operands[0]) {
+                    (Unsized, AddressingMode::Empty) => 0b100,
+                    (_, AddressingMode::Empty) => return Err(Log::SizeOperandMismatch),
+                    (L, _) => 0b011,
+                    _ => 0b010,
+                };
+
+                let mut format = vec![op.op_type.format() & !0b111 | opmode];
+                format.extend(ea_a2);
+                format
             }
 
-            Link => {
-                let reg = ea_a1 & 0b111;
-                let mut format = vec![op.op_type.format() | reg];
+            Callm => {
+                let mut format = vec![op.op_type.format() | ea_b1, ea_a2[0]];
                 format.extend(ea_b2);
                 format
             }
 
+            // the mode and register bits are the D/A flag and register
+            Rtm => vec![op.op_type.format() | (ea_a1 & 0b1111)],
+
             Trap => {
-                let vector = ea_a2[0] & 0b1111;
+                let vector = self.quick(op, 0, 0, 15)?;
                 vec![op.op_type.format() | vector]
             }
 
@@ -691,7 +2294,7 @@ impl Assembler {
                 match self.cpu_type {
                     CpuType::MC68000 => return Err(Log::UnsupportedInstruction),
                     _ => {
-                        let vector = ea_a2[0] & 0b111;
+                        let vector = self.quick(op, 0, 0, 7)?;
                         vec![op.op_type.format() | vector]
                     }
                 }
@@ -724,9 +2327,8 @@ impl Assembler {
             }
 
             Dbcc(_) => {
-                let disp = (ea_b2[0] as i16 - (op.location as i16 + 2)) as u16;
                 let reg = ea_a1 & 0b111;
-                vec![op.op_type.format() | reg, disp]
+                vec![op.op_type.format() | reg, ea_b2[0]]
             }
 
             Movep => {
@@ -759,52 +2361,231 @@ impl Assembler {
                 format
             }
 
-            Data(size, values) => {
-                let mut vec = Vec::new();
+            Data(_, _) | Fill(_, _, _) | Binary(_) | Org(_) | Rorg(_) | End(_) | Align(_, _, _) | Define(_) => unreachable!(), //handled by emit
+        })
+    }
+}
 
-                for value in values {
-                    let number = value.resolve_value(&self.labels, &self.defines)?.to_be_bytes();
+/// Splits a comma separated list, ignoring commas inside parentheses
+fn split_list(text: &str) -> Result<Vec<&str>, Log> {
+    let mut list = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut quotes = Quotes::default();
 
-                    let range = match size {
-                        DataType::Data08 => 7..=7,
-                        DataType::Data16 => 6..=7,
-                        DataType::Data24 => 5..=7,
-                        DataType::Data32 => 4..=7,
-                        DataType::Data64 => 0..=7,
-                    };
+    for (idx, c) in text.char_indices() {
+        if quotes.inside(c) {
+            continue;
+        }
 
-                    vec.extend_from_slice(&number[range]);
-                }
+        match c {
+            '(' => depth += 1,
 
-                if vec.len() & 1 != 0 {
-                    vec.push(0);
+            ')' => {
+                if depth == 0 {
+                    return Err(Log::MismatchedParentheses(text.trim().to_string()));
                 }
 
-                let vec2: Vec<u16> = vec
-                    .chunks_exact(2)
-                    .into_iter()
-                    .map(|x| u16::from_be_bytes([x[0], x[1]]))
-                    .collect();
+                depth -= 1;
+            }
 
-                vec2
+            ',' if depth == 0 => {
+                list.push(text[start..idx].trim());
+                start = idx + 1;
             }
-        })
+
+            _ => (),
+        }
+    }
+
+    if depth != 0 {
+        return Err(Log::MismatchedParentheses(text.trim().to_string()));
+    }
+
+    let last = text[start..].trim();
+
+    if !last.is_empty() || !list.is_empty() {
+        list.push(last);
+    }
+
+    Ok(list)
+}
+
+/// Tracks whether text is inside a quoted string or character constant, skipping escaped characters
+#[derive(Default)]
+struct Quotes {
+    quote: Option<char>,
+    escaped: bool,
+}
+
+impl Quotes {
+    /// Feeds the next character, returns true if it is part of something quoted
+    fn inside(&mut self, c: char) -> bool {
+        let Some(quote) = self.quote else {
+            if c == '"' || c == '\'' {
+                self.quote = Some(c);
+                return true;
+            }
+
+            return false;
+        };
+
+        if self.escaped {
+            self.escaped = false;
+        } else if c == '\\' {
+            self.escaped = true;
+        } else if c == quote {
+            self.quote = None;
+        }
+
+        true
+    }
+}
+
+/// Splits `name = expr`, `!name = expr`, `name equ expr` and `name set expr` into the name,
+/// whether it can be assigned again and the expression
+fn assignment(line: &str) -> Option<(&str, bool, &str)> {
+    let is_name = |name: &str| {
+        name.starts_with(|c: char| c.is_alphabetic() || c == '_') && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    };
+
+    if let Some((name, expr)) = line.split_once('=') {
+        let name = name.trim();
+        let name = name.strip_prefix('!').unwrap_or(name);
+
+        if is_name(name) && !expr.starts_with('=') {
+            return Some((name, false, expr.trim()));
+        }
+    }
+
+    let (name, rest) = line.split_once(char::is_whitespace)?;
+    let name = name.strip_suffix(':').unwrap_or(name);
+    let (directive, expr) = rest.trim_start().split_once(char::is_whitespace).unwrap_or((rest.trim_start(), ""));
+
+    match (is_name(name), directive) {
+        (true, "equ") => Some((name, false, expr.trim())),
+        (true, "set") => Some((name, true, expr.trim())),
+        _ => None,
+    }
+}
+
+/// Index of the quote closing the string or character constant the text starts with
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut quotes = Quotes::default();
+
+    text.char_indices().find(|&(_, c)| {
+        quotes.inside(c);
+        quotes.quote.is_none()
+    }).map(|(idx, _)| idx).filter(|&idx| idx > 0)
+}
+
+/// Bytes of a string in double quotes
+fn parse_string(token: &str) -> Result<Vec<u8>, Log> {
+    match closing_quote(token) {
+        Some(end) if end == token.len() - 1 => unescape(&token[1 .. end], token),
+        Some(_) => Err(Log::InvalidExpression(token.to_string())),
+        None => Err(Log::UnterminatedString(token.to_string())),
+    }
+}
+
+/// Resolves the escape sequences `\n` `\r` `\t` `\0` `\\` `\"` `\'` and `\xNN`
+fn unescape(text: &str, token: &str) -> Result<Vec<u8>, Log> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            bytes.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+
+        bytes.push(match chars.next() {
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('0') => 0,
+            Some('\\') => b'\\',
+            Some('"') => b'"',
+            Some('\'') => b'\'',
+
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+
+                match (hex.len(), u8::from_str_radix(&hex, 16)) {
+                    (2, Ok(byte)) => byte,
+                    _ => return Err(Log::InvalidEscape(token.to_string())),
+                }
+            }
+
+            _ => return Err(Log::InvalidEscape(token.to_string())),
+        });
+    }
+
+    Ok(bytes)
+}
+
+/// Removes a comment from a line, ignoring semicolons in quotes
+pub fn strip_comment(line: &str) -> &str {
+    let mut quotes = Quotes::default();
+
+    match line.char_indices().find(|&(_, c)| !quotes.inside(c) && c == ';') {
+        Some((idx, _)) => &line[.. idx],
+        None => line,
+    }
+}
+
+fn parse_file_name(token: &str) -> Result<&str, Log> {
+    let token = token.trim();
+
+    let name = match token.strip_prefix('"') {
+        Some(quoted) => match quoted.strip_suffix('"') {
+            Some(name) => name,
+            None => return Err(Log::InvalidFileName),
+        },
+
+        None => token,
+    };
+
+    match name.is_empty() {
+        true  => Err(Log::InvalidFileName),
+        false => Ok(name),
     }
 }
 
+/// Parses a number, `0x`/`$` is hexadecimal, `0b`/`%` is binary, `0o`/`@` is octal and `'ABCD'` is characters packed big-endian.
+/// `_` can be used to separate digits
 fn parse_n(token: &str) -> Result<u64, Log> {
-    let (radix, offset_begin) = if token.len() > 2 {
-        match &token[0..2] {
-            "0x" => (16, 2),
-            "0b" => (2, 2),
-            _ => (10, 0),
+    if token.starts_with('\'') {
+        let chars = match closing_quote(token) {
+            Some(end) if end == token.len() - 1 && end > 1 => unescape(&token[1 .. end], token)?,
+            _ => return Err(Log::InvalidNumber),
+        };
+
+        if chars.len() > 8 {
+            return Err(Log::NumberOverflow(token.to_string()));
         }
+
+        return Ok(chars.into_iter().fold(0, |value, c| value << 8 | c as u64));
+    }
+
+    let (radix, digits) = if let Some(hex) = token.strip_prefix("0x").or(token.strip_prefix('$')) {
+        (16, hex)
+    } else if let Some(bin) = token.strip_prefix("0b").or(token.strip_prefix('%')) {
+        (2, bin)
+    } else if let Some(oct) = token.strip_prefix("0o").or(token.strip_prefix('@')) {
+        (8, oct)
     } else {
-        (10, 0)
+        (10, token)
     };
 
-    match u64::from_str_radix(&token[offset_begin..token.len()], radix) {
+    let digits = digits.replace('_', "");
+
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(Log::InvalidNumber);
+    }
+
+    match u64::from_str_radix(&digits, radix) {
         Ok(val) => Ok(val),
-        Err(_) => Err(Log::InvalidNumber),
+        Err(_) => Err(Log::NumberOverflow(token.to_string())), //only digits are left, so it can only overflow
     }
 }
@@ -8,6 +8,11 @@ pub enum OpSize {
 
     Unsized,
     BU, WU, LU,
+    BWU, WLU, BWLU,
+
+    S, D, X, P, // single, double, extended and packed decimal floating point
+    XU, LXU,
+    BWLSDXPU,
 }
 
 impl OpSize {
@@ -27,6 +32,42 @@ impl OpSize {
             OpSize::BU      => 0b1001,
             OpSize::WU      => 0b1010,
             OpSize::LU      => 0b1100,
+
+            OpSize::BWU     => 0b1011,
+            OpSize::WLU     => 0b1110,
+
+            OpSize::BWLU    => 0b1111,
+
+            OpSize::S       => 0b0001_0000,
+            OpSize::D       => 0b0010_0000,
+            OpSize::X       => 0b0100_0000,
+            OpSize::P       => 0b1000_0000,
+
+            OpSize::XU      => 0b0100_1000,
+            OpSize::LXU     => 0b0100_1100,
+            OpSize::BWLSDXPU => 0b1111_1111,
+        }
+    }
+
+    /// The size suffixes included in the size, for messages
+    pub fn suffixes(&self) -> Vec<&'static str> {
+        [(OpSize::B, ".b"), (OpSize::W, ".w"), (OpSize::L, ".l"), (OpSize::S, ".s"), (OpSize::D, ".d"), (OpSize::X, ".x"), (OpSize::P, ".p")].into_iter()
+            .filter(|(size, _)| self.mask() & size.mask() != 0)
+            .map(|(_, suffix)| suffix)
+            .collect()
+    }
+
+    /// Source or destination format of FPU instructions
+    pub fn float_format(&self) -> u16 {
+        match self {
+            OpSize::L => 0b000 << 10,
+            OpSize::S => 0b001 << 10,
+            OpSize::X => 0b010 << 10,
+            OpSize::P => 0b011 << 10,
+            OpSize::W => 0b100 << 10,
+            OpSize::D => 0b101 << 10,
+            OpSize::B => 0b110 << 10,
+            _ => unreachable!(),
         }
     }
 
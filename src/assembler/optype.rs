@@ -2,7 +2,7 @@
 
 use crate::logging::Log;
 
-use super::{addressing::AddressingList, addressing::AddressingMode, OpSize, value::Value, DataType};
+use super::{addressing::AddressingList, addressing::AddressingMode, CpuType, OpSize, value::Value, DataType};
 
 #[derive(Debug)]
 pub enum OpType {
@@ -12,44 +12,90 @@ pub enum OpType {
     AddSubX(bool),
     Bcd(bool), //false = ABCD, true = SBCD
     BitManip(u8),
+    Bitfield(u8),
+    Bitrev(u16), //bitrev, byterev and ff1
     Bkpt,
     Branch(u8),
+    Cache(u8), //push bit and scope, cinv/cpush
+    Callm,
+    Cas,
+    Cas2,
     Chk,
     Cmp,
+    Cmp2(bool), //false = cmp2, true = chk2
     Cmpa,
     Cmpm,
     Dbcc(u8),
+    DivLong(bool), //divul/divsl, true = signed
     Eor,
     Exg,
     Ext,
+    Extb,
+    FBranch(u8),
+    FDbcc(u8),
+    FMove,
+    FMovecr,
+    FMovem,
+    FNop,
+    FOp(u8), //opmode of the arithmetic instructions
+    FRestore,
+    FSave,
+    FScc(u8),
+    FSincos,
+    FTrapcc(u8),
     Immediates(u8),
     Jump(bool), //false = jsr, true = jmp
     Lea,
     Link,
+    Lpstop,
+    Mac(bool), //false = mac, true = msac
     Misc1(u8),
+    Mov3q,
     Move,
+    Move16,
     MoveA,
     Movec,
     Movem,
     Movep,
     MoveQ,
     Moves,
+    Movclr,
     MulDiv(u16),
+    Mvx(bool), //false = mvs, true = mvz
     Nbcd,
     NoOperands(u16),
     OrAnd(bool), //false = or, true = and
+    Pack(bool), //false = pack, true = unpk
     Pea,
+    PFlush(u8), //68040 opmode
+    PFlush030(bool), //true = pflusha
+    PLoad(bool), //false = ploadw, true = ploadr
+    PMove(bool), //true = pmovefd
+    PTest(bool), //false = ptestw, true = ptestr
+    PTest030(bool),
+    Rem(bool), //true = signed
     Rotation(u8, bool),
     Rtd,
+    Rtm,
+    Sats,
     Scc(u8),
     Stop,
     Swap,
     Tas,
+    Tbl(bool, bool), //signed, no rounding
     Trap,
+    TrapCc(u8),
     Tst,
     Unlk,
 
     Data(DataType, Vec<Value>),
+    Fill(DataType, u32, Value), // size, count, value of `ds` and `dcb`
+    Binary(Vec<u8>),
+    Org(u32),
+    Rorg(u32),
+    End(Option<Value>),
+    Align(u32, u32, u8), // alignment, offset, fill byte
+    Define(String), // checks that a define can be resolved
 }
 
 impl OpType {
@@ -73,16 +119,33 @@ impl OpType {
             },
 
             BitManip(format) => (*format as u16) << 6,
+            Bitfield(format) => (0b1110_1_000_11 << 6) | ((*format as u16) << 8),
+            Bitrev(format) => *format,
             Bkpt => 0b0100100001001 << 3,
             Branch(cond) => u16::from_be_bytes([(0b0110 << 4) | cond, 0]),
+            Cache(format) => (0b1111_0100 << 8) | ((*format as u16) << 3),
+            Callm => 0b0000_0110_11 << 6,
+            Cas  => 0b0000_1_00_011 << 6,
+            Cas2 => 0b0000_1_00_011111100,
             Chk  => 0b0100_000_110 << 6,
             Cmp  => 0b1011 << 12,
+            Cmp2(_) => 0b0000_0_00_011 << 6,
             Cmpa => 0b1011_000_0_11 << 6,
             Cmpm => 0b1011_000_1_00_001 << 3,
             Dbcc(cond) => (0b0101_0000_11_001 << 3) | ((*cond as u16) << 8),
+            DivLong(_) | Rem(_) => 0b0100_1100_01 << 6,
             Eor => 0b1011_000_1 << 8,
             Exg  => 0b1100_000_1 << 8,
             Ext  => 0b0100_100_0_1 << 7,
+            Extb => 0b0100_100_111_000 << 3,
+            FBranch(cond) => (0b1111_001_01_0 << 6) | *cond as u16,
+            FDbcc(_) => 0b1111_001_001_001 << 3,
+            FMove | FMovecr | FMovem | FOp(_) | FSincos => 0b1111_001_000 << 6,
+            FNop => 0b1111_001_01_0 << 6,
+            FRestore => 0b1111_001_101 << 6,
+            FSave => 0b1111_001_100 << 6,
+            FScc(_) => 0b1111_001_001 << 6,
+            FTrapcc(_) => 0b1111_001_001_111 << 3,
             Immediates(imm) => (*imm as u16) << 9,
 
             Jump(jxx) => match jxx {
@@ -92,7 +155,9 @@ impl OpType {
 
             Lea  => 0b0100_000_111 << 6,
             Link => 0b0100_111001010 << 3,
+            Mac(_) => 0b1010 << 12,
             Misc1(format) => (*format as u16) << 8,
+            Mov3q => 0b1010_000_101 << 6,
             Move => 0,
             MoveA => 0b001 << 6,
             Movec => 0b010011100111101 << 1,
@@ -100,7 +165,9 @@ impl OpType {
             Movep => 0b1_0_0_001 << 3,
             MoveQ => 0b0111 << 12,
             Moves => 0b0000_1110 << 8,
+            Movclr => 0b1010_000_111 << 6,
             MulDiv(format) => *format,
+            Mvx(zero) => (0b0111_000_10 << 7) | ((*zero as u16) << 7),
             Nbcd => 0b0100_100_000 << 6,
             NoOperands(data) => *data,
 
@@ -109,18 +176,31 @@ impl OpType {
                 true  => 0b1100 << 12,
             },
 
+            Pack(format) => match format {
+                false => 0b1000_000_10100 << 4,
+                true  => 0b1000_000_11000 << 4,
+            },
+
+            Move16 => 0b1111_0110 << 8,
             Pea  => 0b0100_100_001 << 6,
+            PFlush(opmode) => (0b1111_0101_000 << 5) | ((*opmode as u16) << 3),
+            PFlush030(_) | PLoad(_) | PMove(_) | PTest030(_) => 0b1111_0000 << 8,
+            PTest(read) => (0b1111_0101_0100_1 << 3) | ((*read as u16) << 5),
             Rotation(_, _) => 0b1110 << 12,
             Rtd => 0b0100111001110100,
+            Rtm => 0b0000_0110_1100 << 4,
+            Sats => 0b0100_1100_10_000 << 3,
             Scc(cond) => (0b0101_0000_11 << 6) | ((*cond as u16) << 8),
             Stop => 0b0100_111001110010,
             Swap => 0b0100_100_001_000 << 3,
             Tas  => 0b0100_101011 << 6,
+            Lpstop | Tbl(_, _) => 0b1111_1000 << 8,
             Trap => 0b0100_11100100 << 4,
+            TrapCc(cond) => (0b0101_0000_11111 << 3) | ((*cond as u16) << 8),
             Tst  => 0b0100_1010 << 8,
             Unlk => 0b0100_111001011 << 3,
 
-            Data(_, _) => 0, //unused
+            Data(_, _) | Fill(_, _, _) | Binary(_) | Org(_) | Rorg(_) | End(_) | Align(_, _, _) | Define(_) => 0, //unused
         }
     }
 
@@ -179,6 +259,23 @@ impl OpType {
             "sgt" => Scc(0b1110),
             "sle" => Scc(0b1111),
 
+            "trapt"  => TrapCc(0b0000),
+            "trapf" | "tpf" => TrapCc(0b0001),
+            "traphi" => TrapCc(0b0010),
+            "trapls" => TrapCc(0b0011),
+            "trapcc" | "traphs" => TrapCc(0b0100),
+            "trapcs" | "traplo" => TrapCc(0b0101),
+            "trapne" => TrapCc(0b0110),
+            "trapeq" => TrapCc(0b0111),
+            "trapvc" => TrapCc(0b1000),
+            "trapvs" => TrapCc(0b1001),
+            "trappl" => TrapCc(0b1010),
+            "trapmi" => TrapCc(0b1011),
+            "trapge" => TrapCc(0b1100),
+            "traplt" => TrapCc(0b1101),
+            "trapgt" => TrapCc(0b1110),
+            "traple" => TrapCc(0b1111),
+
             "illegal" => NoOperands(0b0100_101011111100),
             "nop"     => NoOperands(0b0100_111001110001),
             "reset"   => NoOperands(0b0100_111001110000),
@@ -186,6 +283,7 @@ impl OpType {
             "rtr"     => NoOperands(0b0100_111001110111),
             "rts"     => NoOperands(0b0100_111001110101),
             "trapv"   => NoOperands(0b0100_111001110110),
+            "bgnd"    => NoOperands(0b0100_101011111010),
 
             "add" => AddSub(false),
             "sub" => AddSub(true),
@@ -206,8 +304,8 @@ impl OpType {
             "eori" => Immediates(0b101),
             "cmpi" => Immediates(0b110),
 
-            "jmp" => Jump(false),
-            "jsr" => Jump(true),
+            "jsr" => Jump(false),
+            "jmp" => Jump(true),
 
             "move" => Move,
             "movea" => MoveA,
@@ -271,132 +369,379 @@ impl OpType {
             "bkpt" => Bkpt,
             "rtd" => Rtd,
             "movec" => Movec,
+            "move16" => Move16,
             "moves" => Moves,
 
-            _ => return Err(Log::InvalidOp),
+            "bftst"  => Bitfield(0b000),
+            "bfextu" => Bitfield(0b001),
+            "bfchg"  => Bitfield(0b010),
+            "bfexts" => Bitfield(0b011),
+            "bfclr"  => Bitfield(0b100),
+            "bfffo"  => Bitfield(0b101),
+            "bfset"  => Bitfield(0b110),
+            "bfins"  => Bitfield(0b111),
+
+            "cas"  => Cas,
+            "cas2" => Cas2,
+            "cmp2" => Cmp2(false),
+            "chk2" => Cmp2(true),
+            "divul" => DivLong(false),
+            "divsl" => DivLong(true),
+            "extb" => Extb,
+            "pack" => Pack(false),
+            "unpk" => Pack(true),
+            "callm" => Callm,
+            "rtm" => Rtm,
+
+            "pflushn"  => PFlush(0b00),
+            "pflush"   => PFlush(0b01),
+            "pflushan" => PFlush(0b10),
+            "pflusha"  => PFlush(0b11),
+            "ploadw" => PLoad(false),
+            "ploadr" => PLoad(true),
+            "pmove"   => PMove(false),
+            "pmovefd" => PMove(true),
+            "ptestw" => PTest(false),
+            "ptestr" => PTest(true),
+
+            "cinvl"  => Cache(0b001),
+            "cinvp"  => Cache(0b010),
+            "cinva"  => Cache(0b011),
+            "cpushl" => Cache(0b101),
+            "cpushp" => Cache(0b110),
+            "cpusha" => Cache(0b111),
+
+            "lpstop" => Lpstop,
+
+            "mov3q"  => Mov3q,
+            "mvs"    => Mvx(false),
+            "mvz"    => Mvx(true),
+            "sats"   => Sats,
+            "rems"   => Rem(true),
+            "remu"   => Rem(false),
+            "bitrev"  => Bitrev(0b0000_000_011_000 << 3),
+            "byterev" => Bitrev(0b0000_001_011_000 << 3),
+            "ff1"     => Bitrev(0b0000_010_011_000 << 3),
+            "mac"    => Mac(false),
+            "msac"   => Mac(true),
+            "movclr" => Movclr,
+            "tblu"  => Tbl(false, false),
+            "tblun" => Tbl(false, true),
+            "tbls"  => Tbl(true, false),
+            "tblsn" => Tbl(true, true),
+
+            "fmove"   => FMove,
+            "fmovem"  => FMovem,
+            "fmovecr" => FMovecr,
+            "fsincos" => FSincos,
+            "fnop"    => FNop,
+            "fsave"   => FSave,
+            "frestore" => FRestore,
+
+            "fint"    => FOp(0x01),
+            "fsinh"   => FOp(0x02),
+            "fintrz"  => FOp(0x03),
+            "fsqrt"   => FOp(0x04),
+            "flognp1" => FOp(0x06),
+            "fetoxm1" => FOp(0x08),
+            "ftanh"   => FOp(0x09),
+            "fatan"   => FOp(0x0A),
+            "fasin"   => FOp(0x0C),
+            "fatanh"  => FOp(0x0D),
+            "fsin"    => FOp(0x0E),
+            "ftan"    => FOp(0x0F),
+            "fetox"   => FOp(0x10),
+            "ftwotox" => FOp(0x11),
+            "ftentox" => FOp(0x12),
+            "flogn"   => FOp(0x14),
+            "flog10"  => FOp(0x15),
+            "flog2"   => FOp(0x16),
+            "fabs"    => FOp(0x18),
+            "fcosh"   => FOp(0x19),
+            "fneg"    => FOp(0x1A),
+            "facos"   => FOp(0x1C),
+            "fcos"    => FOp(0x1D),
+            "fgetexp" => FOp(0x1E),
+            "fgetman" => FOp(0x1F),
+            "fdiv"    => FOp(0x20),
+            "fmod"    => FOp(0x21),
+            "fadd"    => FOp(0x22),
+            "fmul"    => FOp(0x23),
+            "fsgldiv" => FOp(0x24),
+            "frem"    => FOp(0x25),
+            "fscale"  => FOp(0x26),
+            "fsglmul" => FOp(0x27),
+            "fsub"    => FOp(0x28),
+            "fcmp"    => FOp(0x38),
+            "ftst"    => FOp(0x3A),
+
+            name => match fpu_conditional(name) {
+                Some(op_type) => op_type,
+                None => return Err(Log::InvalidOp),
+            },
         })
     }
 
-    pub fn valid_size(&self, size: OpSize) -> Result<(), Log> {
+    pub fn valid_size(&self, size: OpSize, cpu_type: &CpuType) -> Result<(), Log> {
+        if size.mask() & self.cpu_sizes(cpu_type).mask() != 0 {
+            Ok(())
+        } else if size.mask() & self.sizes().mask() != 0 {
+            Err(Log::CpuTypeSizeNotValid)
+        } else {
+            Err(Log::UnsupportedSuffix)
+        }
+    }
+
+    /// Sizes on the target CPU. ColdFire leaves out most byte and word arithmetic, and ISA_A long branches
+    pub fn cpu_sizes(&self, cpu_type: &CpuType) -> OpSize {
+        use OpType::*;
+
+        if !cpu_type.is_coldfire() {
+            return self.sizes();
+        }
+
+        let isa_a = matches!(cpu_type, CpuType::ColdFireA);
+
+        match self {
+            Misc1(0b0100_0010) => self.sizes(), // clr
+            Cmp | Cmpa | Immediates(0b110) if !isa_a => self.sizes(),
+            Branch(_) if isa_a => OpSize::BWU,
+
+            AddSub(_) | AddSubA(_) | AddSubQ(_) | AddSubX(_) | Cmp | Cmpa | Eor | Immediates(_) |
+            Misc1(_) | Movem | OrAnd(_) | Rotation(_, _) => OpSize::L,
+
+            Link => OpSize::W,
+            _ => self.sizes(),
+        }
+    }
+
+    /// All sizes the instruction can be used with
+    pub fn sizes(&self) -> OpSize {
         use OpSize::*;
         use OpType::*;
 
-        let valid = match self {
-            Chk | Link | MulDiv(_) => W,
+        match self {
+            Chk => W,
+            DivLong(_) | Extb | Rem(_) => L,
 
-            Branch(_) => BW,
+            Branch(_) => BWLU,
             BitManip(_) => BL,
-            AddSubA(_) | Cmpa | Ext | MoveA | Movem | Movep => WL,
+            AddSubA(_) | Cmpa | Ext | MoveA | Movem | Movep | Link | MulDiv(_) | Cas2 | Mac(_) => WL,
+            Mvx(_) => BW,
+            TrapCc(_) | PMove(_) | FBranch(_) | FTrapcc(_) => WLU,
+            FMove | FOp(_) | FSincos => BWLSDXPU,
+            FMovem => LXU,
+            FMovecr => XU,
+            FScc(_) => BU,
+            FDbcc(_) | FNop | FSave | FRestore => Unsized,
 
             AddSub(_) | AddSubQ(_) | AddSubX(_) | Cmp | Cmpm | Eor | Immediates(_) |
-            Misc1(_) | Move | OrAnd(_) | Rotation(_, _) | Tst | Moves => BWL,
+            Misc1(_) | Move | OrAnd(_) | Rotation(_, _) | Tst | Moves | Cas | Cmp2(_) | Tbl(_, _) => BWL,
 
-            Jump(_) | NoOperands(_) | Stop | Trap | Unlk | Bkpt | Rtd => Unsized,
+            Jump(_) | NoOperands(_) | Stop | Trap | Unlk | Bkpt | Rtd |
+            Bitfield(_) | Pack(_) | Callm | Rtm |
+            Cache(_) | Lpstop | Move16 | PFlush(_) | PFlush030(_) | PLoad(_) | PTest(_) | PTest030(_) => Unsized,
 
             Bcd(_) | Nbcd | Scc(_) | Tas => BU,
             Dbcc(_) | Swap => WU,
             Exg | Lea | MoveQ | Pea | Movec => LU,
+            Bitrev(_) | Mov3q | Movclr | Sats => LU,
 
-            Data(_, _) => Unsized, //unused
-        };
-
-        match size.mask() & valid.mask() != 0 {
-            true  => Ok(()),
-            false => Err(Log::UnsupportedSuffix),
+            Data(_, _) | Fill(_, _, _) | Binary(_) | Org(_) | Rorg(_) | End(_) | Align(_, _, _) | Define(_) => Unsized, //unused
         }
     }
 
-    fn mode_lists(&self, modes: &[AddressingMode; 2]) -> [Option<AddressingList>; 2] {
+    fn mode_lists(&self, modes: &[AddressingMode; 3]) -> [Option<AddressingList>; 3] {
         use AddressingList::*;
         use OpType::*;
 
         match self {
-            Branch(_)                      => [Some(Displacement), None],
-            NoOperands(_)                  => [None, None],
-            AddSubA(_) | MoveA | Cmpa      => [Some(All), Some(AddressRegister)],
-            AddSubQ(_)                     => [Some(DataQuick), Some(Alterable)],
-            Jump(_) | Pea                  => [Some(Control), None],
-            MoveQ                          => [Some(DataQuick), Some(DataRegister)],
-            Lea                            => [Some(Control), Some(AddressRegister)],
-            Chk                            => [Some(DataAddressing), Some(DataRegister)],
-            Tst                            => [Some(All), None],
-            Ext | Swap                     => [Some(DataRegister), None],
-            Unlk                           => [Some(AddressRegister), None],
-            Link                           => [Some(AddressRegister), Some(Immediate)],
-            Trap | Bkpt                    => [Some(DataQuick), None],
-            Stop | Rtd                     => [Some(Immediate), None],
-            Cmp                            => [Some(All), Some(DataRegister)],
-            Cmpm                           => [Some(AddressPostincrement), Some(AddressPostincrement)],
-            MulDiv(_)                      => [Some(DataAlterable), Some(DataRegister)],
-            Eor                            => [Some(DataRegister), Some(DataAlterable)],
-            Dbcc(_)                        => [Some(DataRegister), Some(Displacement)],
-            Misc1(_) | Tas | Scc(_) | Nbcd => [Some(DataAlterable), None],
+            Branch(_)                      => [Some(Displacement), None, None],
+            NoOperands(_)                  => [None, None, None],
+            AddSubA(_) | MoveA | Cmpa      => [Some(All), Some(AddressRegister), None],
+            AddSubQ(_)                     => [Some(DataQuick), Some(Alterable), None],
+            Jump(_) | Pea                  => [Some(Control), None, None],
+            MoveQ                          => [Some(DataQuick), Some(DataRegister), None],
+            Lea                            => [Some(Control), Some(AddressRegister), None],
+            Chk                            => [Some(DataAddressing), Some(DataRegister), None],
+            Tst                            => [Some(All), None, None],
+            Ext | Swap                     => [Some(DataRegister), None, None],
+            Unlk                           => [Some(AddressRegister), None, None],
+            Link                           => [Some(AddressRegister), Some(Immediate), None],
+            Trap | Bkpt                    => [Some(DataQuick), None, None],
+            Stop | Rtd                     => [Some(Immediate), None, None],
+            Cmp                            => [Some(All), Some(DataRegister), None],
+            Cmpm                           => [Some(AddressPostincrement), Some(AddressPostincrement), None],
+            MulDiv(_)                      => [Some(DataAddressing), Some(DataRegisterPair), None],
+            Eor                            => [Some(DataRegister), Some(DataAlterable), None],
+            Dbcc(_)                        => [Some(DataRegister), Some(Displacement), None],
+            Extb                           => [Some(DataRegister), None, None],
+            Cas                            => [Some(DataRegister), Some(DataRegister), Some(MemoryAlterable)],
+            Cas2                           => [Some(RegisterPair), Some(RegisterPair), Some(IndirectPair)],
+            Cmp2(_)                        => [Some(Control), Some(Register), None],
+            DivLong(_)                     => [Some(DataAddressing), Some(RegisterPair), None],
+            Callm                          => [Some(Immediate), Some(Control), None],
+            Rtm                            => [Some(Register), None, None],
+            Lpstop                         => [Some(Immediate), None, None],
+            Move16                         => [Some(Move16Operand), Some(Move16Operand), None],
+            PFlush(0b00 | 0b01)            => [Some(AddressIndirect), None, None],
+            PFlush(_) | PFlush030(true)    => [None, None, None],
+            PLoad(_)                       => [Some(FunctionCode), Some(ControlAlterable), None],
+            PTest(_)                       => [Some(AddressIndirect), None, None],
+            PTest030(_)                    => [Some(FunctionCode), Some(ControlAlterable), Some(DataQuick)],
+            Cache(0b011 | 0b111)           => [Some(Caches), None, None],
+            Cache(_)                       => [Some(Caches), Some(AddressIndirect), None],
+            Misc1(_) | Tas | Scc(_) | Nbcd => [Some(DataAlterable), None, None],
+            Bitrev(_) | Sats               => [Some(DataRegister), None, None],
+            Mov3q                          => [Some(DataQuick), Some(Alterable), None],
+            Mvx(_)                         => [Some(All), Some(DataRegister), None],
+            Rem(_)                         => [Some(DataIndirect), Some(RegisterPair), None],
+            Movclr                         => [Some(Accumulator), Some(Register), None],
+
+            // mac and msac with a load keep the loaded address in the second operand
+            Mac(_) => match modes[1] {
+                AddressingMode::Empty => [Some(MacPair), None, Some(Accumulator)],
+                _ => [Some(MacPair), Some(Indirect), Some(MacLoad)],
+            },
+
+            // the bitfield is attached to the effective address, and is stored as the last operand
+            Bitfield(0b000) => [Some(DataControl), Some(OffsetWidth), None],
+            Bitfield(0b010 | 0b100 | 0b110) => [Some(DataControlAlterable), Some(OffsetWidth), None],
+            Bitfield(0b111) => [Some(DataRegister), Some(DataControlAlterable), Some(OffsetWidth)],
+            Bitfield(_) => [Some(DataControl), Some(DataRegister), Some(OffsetWidth)],
+
+            PFlush030(false) => match modes[2] {
+                AddressingMode::Empty => [Some(FunctionCode), Some(DataQuick), None],
+                _ => [Some(FunctionCode), Some(DataQuick), Some(ControlAlterable)],
+            },
+
+            PMove(_) => match modes[0] {
+                AddressingMode::ControlReg(_) => [Some(ControlRegister), Some(ControlAlterable), None],
+                _ => [Some(ControlAlterable), Some(ControlRegister), None],
+            },
+
+            Tbl(_, _) => match modes[0] {
+                AddressingMode::RegisterPair(_, _) => [Some(RegisterPair), Some(DataRegister), None],
+                _ => [Some(Control), Some(DataRegister), None],
+            },
+
+            FOp(0x3A) => [Some(FloatSource), None, None], // ftst
+
+            // single operand forms of monadic instructions work on a register in place
+            FOp(opmode) => match (&modes[0], &modes[1]) {
+                (AddressingMode::FloatRegister(_), AddressingMode::Empty) if *opmode < 0x20 => [Some(FloatRegister), None, None],
+                _ => [Some(FloatSource), Some(FloatRegister), None],
+            },
+
+            FMove => match (&modes[0], &modes[1]) {
+                (AddressingMode::FloatControl(_), _) => [Some(FloatControl), Some(Alterable), None],
+                (_, AddressingMode::FloatControl(_)) => [Some(All), Some(FloatControl), None],
+                (AddressingMode::FloatRegister(_), AddressingMode::FloatRegister(_)) => [Some(FloatRegister), Some(FloatRegister), None],
+                (AddressingMode::FloatRegister(_), _) => [Some(FloatRegister), Some(DataAlterable), None],
+                _ => [Some(FloatSource), Some(FloatRegister), None],
+            },
+
+            FMovem => match (&modes[0], &modes[1]) {
+                (AddressingMode::FloatControl(_), _) => [Some(FloatControl), Some(Alterable), None],
+                (_, AddressingMode::FloatControl(_)) => [Some(All), Some(FloatControl), None],
+                (AddressingMode::FloatRegister(_) | AddressingMode::FloatRegisterList(_) | AddressingMode::DataRegister(_), _) => [Some(DynamicFloatList), Some(MovemDst), None],
+                _ => [Some(MovemSrc), Some(DynamicFloatList), None],
+            },
+
+            FSincos  => [Some(FloatSource), Some(FloatPair), None],
+            FMovecr  => [Some(DataQuick), Some(FloatRegister), None],
+            FBranch(_) => [Some(Displacement), None, None],
+            FDbcc(_) => [Some(DataRegister), Some(Displacement), None],
+            FScc(_)  => [Some(DataAlterable), None, None],
+            FNop     => [None, None, None],
+            FSave    => [Some(MovemDst), None, None],
+            FRestore => [Some(MovemSrc), None, None],
+
+            TrapCc(_) | FTrapcc(_) => match modes[0] {
+                AddressingMode::Empty => [None, None, None],
+                _ => [Some(Immediate), None, None],
+            },
+
+            Pack(_) => match modes[0] {
+                AddressingMode::DataRegister(_) => [Some(DataRegister), Some(DataRegister), Some(Immediate)],
+                _ => [Some(AddressPredecrement), Some(AddressPredecrement), Some(Immediate)],
+            },
 
             AddSub(_) => match modes[1] {
-                AddressingMode::DataRegister(_) => [Some(All), Some(DataRegister)],
-                _ => [Some(DataRegister), Some(MemoryAlterable)],
+                AddressingMode::DataRegister(_) => [Some(All), Some(DataRegister), None],
+                _ => [Some(DataRegister), Some(MemoryAlterable), None],
             },
 
             AddSubX(_) | Bcd(_) => match modes[0] {
-                AddressingMode::DataRegister(_) => [Some(DataRegister), Some(DataRegister)],
-                _ => [Some(AddressPredecrement), Some(AddressPredecrement)],
+                AddressingMode::DataRegister(_) => [Some(DataRegister), Some(DataRegister), None],
+                _ => [Some(AddressPredecrement), Some(AddressPredecrement), None],
             },
 
             BitManip(_) => match modes[0] {
-                AddressingMode::DataRegister(_) => [Some(DataRegister), Some(DataAddressing)],
-                _ => [Some(Immediate), Some(DataAddressing2)],
+                AddressingMode::DataRegister(_) => [Some(DataRegister), Some(DataAddressing), None],
+                _ => [Some(Immediate), Some(DataAddressing2), None],
             },
 
             OrAnd(_) => match modes[1] {
-                AddressingMode::DataRegister(_) => [Some(DataAddressing), Some(DataRegister)],
-                _ => [Some(DataRegister), Some(MemoryAlterable)],
+                AddressingMode::DataRegister(_) => [Some(DataAddressing), Some(DataRegister), None],
+                _ => [Some(DataRegister), Some(MemoryAlterable), None],
             },
 
             Rotation(_, _) => match modes[1] {
-                AddressingMode::DataRegister(_) => [Some(DataRegisterDataQuick), Some(DataRegister)],
-                _ => [Some(MemoryAlterable), None],
+                AddressingMode::DataRegister(_) => [Some(DataRegisterDataQuick), Some(DataRegister), None],
+                _ => [Some(MemoryAlterable), None, None],
             },
 
             Exg => match modes[1] {
-                AddressingMode::DataRegister(_) => [Some(DataRegister), Some(DataRegister)],
-                _ => [Some(Register), Some(AddressRegister)],
+                AddressingMode::DataRegister(_) => [Some(DataRegister), Some(DataRegister), None],
+                _ => [Some(Register), Some(AddressRegister), None],
             },
 
             Movep => match modes[0] {
-                AddressingMode::DataRegister(_) => [Some(DataRegister), Some(AddressDisplacement)],
-                _ => [Some(AddressDisplacement), Some(DataRegister)],
+                AddressingMode::DataRegister(_) => [Some(DataRegister), Some(AddressDisplacement), None],
+                _ => [Some(AddressDisplacement), Some(DataRegister), None],
             },
 
             Movem => match modes[0] {
-                AddressingMode::RegisterList(_) => [Some(RegisterList), Some(MovemDst)],
-                _ => [Some(MovemSrc), Some(RegisterList)],
+                AddressingMode::RegisterList(_) => [Some(RegisterList), Some(MovemDst), None],
+                _ => [Some(MovemSrc), Some(RegisterList), None],
             },
 
             Move => {
+                match (&modes[0], &modes[1]) {
+                    (AddressingMode::Accumulator(_), AddressingMode::Accumulator(_)) => return [Some(Accumulator), Some(Accumulator), None],
+                    (AddressingMode::Accumulator(_), _) => return [Some(Accumulator), Some(Register), None],
+                    (AddressingMode::MacControl(_), AddressingMode::CCR) => return [Some(MacControl), Some(CCR), None],
+                    (AddressingMode::MacControl(_), _) => return [Some(MacControl), Some(Register), None],
+                    (_, AddressingMode::Accumulator(_)) => return [Some(RegisterImmediate), Some(Accumulator), None],
+                    (_, AddressingMode::MacControl(_)) => return [Some(RegisterImmediate), Some(MacControl), None],
+                    _ => (),
+                }
+
                 match modes[0] {
-                    AddressingMode::CCR => [Some(CCR), Some(DataAlterable)],
-                    AddressingMode::SR => [Some(SR), Some(DataAlterable)],
-                    AddressingMode::USP => [Some(USP), Some(DataRegister)],
+                    AddressingMode::CCR => [Some(CCR), Some(DataAlterable), None],
+                    AddressingMode::SR => [Some(SR), Some(DataAlterable), None],
+                    AddressingMode::USP => [Some(USP), Some(AddressRegister), None],
 
                     _ => {
                         match modes[1] {
-                            AddressingMode::CCR => [Some(DataAddressing), Some(CCR)],
-                            AddressingMode::SR => [Some(DataAddressing), Some(SR)],
-                            AddressingMode::USP => [Some(DataRegister), Some(USP)],
-                            _ => [Some(All), Some(DataAlterable)], //normal move
+                            AddressingMode::CCR => [Some(DataAddressing), Some(CCR), None],
+                            AddressingMode::SR => [Some(DataAddressing), Some(SR), None],
+                            AddressingMode::USP => [Some(AddressRegister), Some(USP), None],
+                            _ => [Some(All), Some(DataAlterable), None], //normal move
                         }
                     }
                 }
             }
 
             Movec => match modes[0] {
-                AddressingMode::DataRegister(_) | AddressingMode::AddressRegister(_) => [Some(Register), Some(ControlRegister)],
-                _ => [Some(ControlRegister), Some(Register)],
+                AddressingMode::DataRegister(_) | AddressingMode::AddressRegister(_) => [Some(Register), Some(ControlRegister), None],
+                _ => [Some(ControlRegister), Some(Register), None],
             }
 
             Moves => match modes[0] {
-                AddressingMode::DataRegister(_) | AddressingMode::AddressRegister(_) => [Some(Register), Some(MemoryAlterable)],
-                _ => [Some(MemoryAlterable), Some(Register)],
+                AddressingMode::DataRegister(_) | AddressingMode::AddressRegister(_) => [Some(Register), Some(MemoryAlterable), None],
+                _ => [Some(MemoryAlterable), Some(Register), None],
             }
 
             Immediates(imm) => {
@@ -404,21 +749,152 @@ impl OpType {
 
                 if valid_ccr_sr_modes.contains(imm) {
                     match modes[1] {
-                        AddressingMode::CCR => [Some(Immediate), Some(CCR)],
-                        AddressingMode::SR => [Some(Immediate), Some(SR)],
-                        _ => [Some(Immediate), Some(DataAlterable)],
+                        AddressingMode::CCR => [Some(Immediate), Some(CCR), None],
+                        AddressingMode::SR => [Some(Immediate), Some(SR), None],
+                        _ => [Some(Immediate), Some(DataAlterable), None],
                     }
                 } else {
-                    [Some(Immediate), Some(DataAlterable)]
+                    [Some(Immediate), Some(DataAlterable), None]
                 }
             }
 
-            Data(_, _) => [None, None], //unused
+            Data(_, _) | Fill(_, _, _) | Binary(_) | Org(_) | Rorg(_) | End(_) | Align(_, _, _) | Define(_) => [None, None, None], //unused
         }
     }
 
-    pub fn is_valid_modes(&self, modes: &[AddressingMode; 2]) -> Result<(), Log> {
-        for (valid_list, mode) in self.mode_lists(modes).iter().zip(modes) {
+    /// Instructions with a third operand
+    pub fn has_third_operand(&self) -> bool {
+        use OpType::*;
+        matches!(self, Cas | Cas2 | Pack(_) | PFlush030(_) | PTest030(_) | Mac(_))
+    }
+
+    /// ColdFire only allows a subset of the modes of some instructions, on top of the usual ones
+    fn coldfire_mode_lists(&self, size: OpSize, modes: &[AddressingMode; 3]) -> Option<[Option<AddressingList>; 3]> {
+        use AddressingList::*;
+        use OpType::*;
+
+        Some(match self {
+            Immediates(_) => [Some(Immediate), Some(DataRegister), None],
+            Misc1(0b0100_0010) => return None, // clr
+            Misc1(_) | Scc(_) => [Some(DataRegister), None, None],
+            AddSubX(_) => [Some(DataRegister), Some(DataRegister), None],
+            Rotation(_, _) => [Some(DataRegisterDataQuick), Some(DataRegister), None],
+            MulDiv(_) if size == OpSize::L => [Some(DataIndirect), Some(DataRegister), None],
+            MulDiv(_) => [Some(DataAddressing), Some(DataRegister), None],
+            Movec => [Some(Register), Some(ControlRegister), None],
+
+            BitManip(_) => match modes[0] {
+                AddressingMode::DataRegister(_) => return None,
+                _ => [Some(Immediate), Some(DataIndirect), None],
+            },
+
+            Movem => match modes[0] {
+                AddressingMode::RegisterList(_) => [Some(RegisterList), Some(AddressOrDisplacement), None],
+                _ => [Some(AddressOrDisplacement), Some(RegisterList), None],
+            },
+
+            Move => match (&modes[0], &modes[1]) {
+                (AddressingMode::MacControl(_), _) => return None,
+                (AddressingMode::CCR, _) => [Some(CCR), Some(DataRegister), None],
+                (AddressingMode::SR, _) => [Some(SR), Some(DataRegister), None],
+                (_, AddressingMode::CCR) => [Some(DataRegisterImmediate), Some(CCR), None],
+                (_, AddressingMode::SR) => [Some(DataRegisterImmediate), Some(SR), None],
+                _ => return None,
+            },
+
+            _ => return None,
+        })
+    }
+
+    /// Instructions of the ColdFire ISAs. Everything else is left out, as are the 68k only instructions
+    fn coldfire_supported(&self, cpu_type: &CpuType) -> bool {
+        use CpuType::*;
+        use OpType::*;
+
+        match self {
+            AddSub(_) | AddSubA(_) | AddSubQ(_) | AddSubX(_) | BitManip(_) | Branch(_) | Cmp | Cmpa | Eor | Ext | Extb |
+            Immediates(_) | Jump(_) | Lea | Link | Misc1(_) | Move | MoveA | Movec | Movem | MoveQ | MulDiv(_) | OrAnd(_) |
+            Pea | Scc(_) | Stop | Swap | Trap | Tst | Unlk | Rem(_) | Mac(_) | Movclr => true,
+
+            Rotation(rot_type, _) => *rot_type <= 0b01, // asl, asr, lsl and lsr
+            TrapCc(cond) => *cond == 0b0001, // tpf
+
+            // illegal, nop, rte and rts
+            NoOperands(data) => matches!(data, 0b0100_101011111100 | 0b0100_111001110001 | 0b0100_111001110011 | 0b0100_111001110101),
+
+            Mov3q | Mvx(_) | Sats | Tas => matches!(cpu_type, ColdFireB | ColdFireC),
+            Bitrev(_) => matches!(cpu_type, ColdFireC),
+            _ => false,
+        }
+    }
+
+    /// 68881 and 68882 instructions
+    pub fn is_fpu(&self) -> bool {
+        use OpType::*;
+        matches!(self, FBranch(_) | FDbcc(_) | FMove | FMovecr | FMovem | FNop | FOp(_) | FRestore | FSave | FScc(_) | FSincos | FTrapcc(_))
+    }
+
+    /// Instructions that only exist on some CPUs
+    pub fn is_supported(&self, cpu_type: &CpuType) -> bool {
+        use CpuType::*;
+        use OpType::*;
+
+        if cpu_type.is_coldfire() {
+            return self.coldfire_supported(cpu_type);
+        }
+
+        match self {
+            Callm | Rtm => matches!(cpu_type, MC68020),
+            Bitfield(_) | Cas | Cas2 | Pack(_) => matches!(cpu_type, MC68020 | MC68030 | MC68040 | MC68060),
+            Cmp2(_) | DivLong(_) | Extb | TrapCc(_) => !matches!(cpu_type, MC68000 | MC68010),
+            PFlush030(_) | PLoad(_) | PMove(_) | PTest030(_) => matches!(cpu_type, MC68030),
+            PFlush(_) | Cache(_) | Move16 => matches!(cpu_type, MC68040 | MC68060),
+            op_type if op_type.is_fpu() => matches!(cpu_type, MC68020 | MC68030 | MC68040 | MC68060),
+            PTest(_) => matches!(cpu_type, MC68040),
+            Lpstop => matches!(cpu_type, MC68060 | CPU32),
+            Tbl(_, _) | NoOperands(0b0100_101011111010) => matches!(cpu_type, CPU32), // bgnd
+            Bitrev(_) | Mac(_) | Mov3q | Movclr | Mvx(_) | Rem(_) | Sats => false,
+            _ => true,
+        }
+    }
+
+    /// The 68030 MMU instructions share names with the 68040 ones, but take different operands
+    pub fn for_cpu(self, cpu_type: &CpuType) -> Self {
+        match (self, cpu_type) {
+            (OpType::PFlush(0b01), CpuType::MC68030) => OpType::PFlush030(false),
+            (OpType::PFlush(0b11), CpuType::MC68030) => OpType::PFlush030(true),
+            (OpType::PTest(read), CpuType::MC68030) => OpType::PTest030(read),
+            (op_type, _) => op_type,
+        }
+    }
+
+    /// Number of words between the opcode and the extension words of the operands
+    pub fn extra_words(&self, size: OpSize, modes: &[AddressingMode; 3]) -> u32 {
+        use OpType::*;
+
+        match self {
+            Cas2 => 2,
+            Bitfield(_) | Cas | Cmp2(_) | DivLong(_) | Movec | Moves | Rem(_) | Mac(_) => 1,
+            PFlush030(_) | PLoad(_) | PMove(_) | PTest030(_) | Lpstop | Tbl(_, _) => 1,
+            FMove | FMovecr | FMovem | FOp(_) | FSincos | FScc(_) | FDbcc(_) | FTrapcc(_) | FNop => 1,
+            MulDiv(_) if size == OpSize::L => 1,
+            Move16 if matches!(modes[1], AddressingMode::AddressPostincrement(_)) && matches!(modes[0], AddressingMode::AddressPostincrement(_)) => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn is_valid_modes(&self, modes: &[AddressingMode; 3], size: OpSize, cpu_type: &CpuType) -> Result<(), Log> {
+        Self::check_modes(&self.mode_lists(modes), modes)?;
+
+        // modes that are fine on the 68k but not on ColdFire
+        match self.coldfire_mode_lists(size, modes) {
+            Some(lists) if cpu_type.is_coldfire() => Self::check_modes(&lists, modes).map_err(|_| Log::CpuTypeModeNotValid),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_modes(lists: &[Option<AddressingList>; 3], modes: &[AddressingMode; 3]) -> Result<(), Log> {
+        for (valid_list, mode) in lists.iter().zip(modes) {
             if let Some(valid_list) = valid_list {
                 if !valid_list.contains(mode) {
                     return Err(Log::InvalidAddressingMode);
@@ -431,3 +907,33 @@ impl OpType {
         Ok(())
     }
 }
+
+/// FBcc, FDBcc, FScc and FTRAPcc with one of the 32 FPU conditions, and the fbra and fdbra aliases
+fn fpu_conditional(name: &str) -> Option<OpType> {
+    const CONDITIONS: [&str; 32] = [
+        "f", "eq", "ogt", "oge", "olt", "ole", "ogl", "or", "un", "ueq", "ugt", "uge", "ult", "ule", "ne", "t",
+        "sf", "seq", "gt", "ge", "lt", "le", "gl", "gle", "ngle", "ngl", "nle", "nlt", "nge", "ngt", "sne", "st",
+    ];
+
+    let condition = |cond: &str| CONDITIONS.iter().position(|c| *c == cond).map(|cond| cond as u8);
+
+    if let Some(cond) = name.strip_prefix("fdb") {
+        return match cond {
+            "ra" => Some(OpType::FDbcc(0b0000)),
+            _ => condition(cond).map(OpType::FDbcc),
+        };
+    }
+
+    if let Some(cond) = name.strip_prefix("ftrap") {
+        return condition(cond).map(OpType::FTrapcc);
+    }
+
+    if let Some(cond) = name.strip_prefix("fb") {
+        return match cond {
+            "ra" => Some(OpType::FBranch(0b1111)),
+            _ => condition(cond).map(OpType::FBranch),
+        };
+    }
+
+    name.strip_prefix("fs").and_then(condition).map(OpType::FScc)
+}
@@ -2,142 +2,424 @@ use std::collections::HashMap;
 
 use crate::logging::Log;
 
-use super::parse_n;
+use super::{closing_quote, parse_n};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Operator {
+    Mul, Div, Mod,
     Add, Sub,
+    Shl, Shr,
+    Less, LessEqual, Greater, GreaterEqual,
+    Equal, NotEqual,
+    And, Xor, Or,
+    LogicalAnd, LogicalOr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Neg, Not, LogicalNot,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Number(u64),
+    Float(f64),
     Label(String),
     Define(String),
 
-    Expression(Vec<Value>),
-    Operator(Operator),
+    Unary(UnaryOperator, Box<Value>),
+    Binary(Operator, Box<Value>, Box<Value>),
+}
+
+impl Operator {
+    /// Higher binds tighter, same order as C
+    fn precedence(&self) -> u8 {
+        use Operator::*;
+
+        match self {
+            Mul | Div | Mod => 10,
+            Add | Sub => 9,
+            Shl | Shr => 8,
+            Less | LessEqual | Greater | GreaterEqual => 7,
+            Equal | NotEqual => 6,
+            And => 5,
+            Xor => 4,
+            Or => 3,
+            LogicalAnd => 2,
+            LogicalOr => 1,
+        }
+    }
+
+    /// Division, shifts and comparisons treat values as signed
+    fn apply(&self, a: u64, b: u64) -> Result<u64, Log> {
+        use Operator::*;
+
+        let (sa, sb) = (a as i64, b as i64);
+
+        Ok(match self {
+            Mul => a.wrapping_mul(b),
+            Div | Mod if b == 0 => return Err(Log::DivisionByZero),
+            Div => sa.wrapping_div(sb) as u64,
+            Mod => sa.wrapping_rem(sb) as u64,
+            Add => a.wrapping_add(b),
+            Sub => a.wrapping_sub(b),
+            Shl => a.checked_shl(b.min(64) as u32).unwrap_or(0),
+            Shr => (sa >> b.min(63)) as u64,
+            Less => (sa < sb) as u64,
+            LessEqual => (sa <= sb) as u64,
+            Greater => (sa > sb) as u64,
+            GreaterEqual => (sa >= sb) as u64,
+            Equal => (a == b) as u64,
+            NotEqual => (a != b) as u64,
+            And => a & b,
+            Xor => a ^ b,
+            Or => a | b,
+            LogicalAnd | LogicalOr => unreachable!(), //short circuited in resolve_value
+        })
+    }
 }
 
 impl Value {
-    pub fn resolve_value(&self, labels: &HashMap<String, u32>, defines: &HashMap<String, u64>) -> Result<u64, Log> {
+    /// Defines are resolved when they are used, so they can refer to labels and defines that come after them
+    pub fn resolve_value(&self, labels: &HashMap<String, u32>, defines: &HashMap<String, Value>) -> Result<u64, Log> {
+        self.resolve(labels, defines, &mut Vec::new())
+    }
+
+    fn resolve(&self, labels: &HashMap<String, u32>, defines: &HashMap<String, Value>, visiting: &mut Vec<String>) -> Result<u64, Log> {
         match self {
             Value::Number(num) => Ok(*num),
+            Value::Float(_) => Err(Log::UnexpectedFloat),
 
+            // names from equ and set are used without !
             Value::Label(label) => match labels.get(label) {
                 Some(val) => Ok(*val as u64),
-                None => Err(Log::NoLabel),
+                None if defines.contains_key(label) => resolve_define(label, labels, defines, visiting),
+                None => Err(Log::NoLabel(label.clone())),
             },
 
-            Value::Define(name) => match defines.get(name) {
-                Some(val) => Ok(*val),
-                None => Err(Log::NoDefine),
-            },
+            Value::Define(name) => resolve_define(name, labels, defines, visiting),
 
-            Value::Expression(values) => {
-                let mut values2 = values.to_owned();
-                let last = values.len() - 1;
-
-                while values2.len() != 1 {
-                    for idx in 0 .. values2.len() {
-                        match values2[idx].to_owned() {
-                            Value::Operator(op) => match op {
-                                Operator::Add => {
-                                    if idx != 0 && idx < last {
-                                        let second = values2.remove(idx + 1).resolve_value(labels, defines)?;
-                                        let first = values2.remove(idx - 1).resolve_value(labels, defines)?;
-                                        let result = first.wrapping_add(second);
-                                        values2[idx - 1] = Value::Number(result);
-                                        break;
-                                    } else {
-                                        todo!()
-                                    }
-                                }
-
-                                Operator::Sub => {
-                                    let is_operator = |x: &Value| -> bool {
-                                        match x {
-                                            Value::Operator(_) => true,
-                                            _ => false,
-                                        }
-                                    };
-
-                                    if idx < last {
-                                        if idx == 0 || is_operator(&values2[idx - 1]) {
-                                            //unary operator -
-                                            let val = values2.remove(idx + 1).resolve_value(labels, defines)?;
-                                            let val2 = -(val as i64);
-                                            values2[idx] = Value::Number(val2 as u64);
-                                            break;
-                                        } else { //might need to put conditions here
-                                            let second = values2.remove(idx + 1).resolve_value(labels, defines)?;
-                                            let first = values2.remove(idx - 1).resolve_value(labels, defines)?;
-                                            let result = first.wrapping_sub(second);
-                                            values2[idx - 1] = Value::Number(result);
-                                            break;
-                                        }
-                                    } else {
-                                        todo!()
-                                    }
-                                }
-                            }
-
-                            _ => (),
-                        }
-                    }
+            Value::Unary(op, value) => {
+                let value = value.resolve(labels, defines, visiting)?;
+
+                Ok(match op {
+                    UnaryOperator::Neg => value.wrapping_neg(),
+                    UnaryOperator::Not => !value,
+                    UnaryOperator::LogicalNot => (value == 0) as u64,
+                })
+            }
+
+            Value::Binary(op, a, b) => {
+                let a = a.resolve(labels, defines, visiting)?;
+
+                match op {
+                    Operator::LogicalAnd if a == 0 => Ok(0),
+                    Operator::LogicalOr if a != 0 => Ok(1),
+                    Operator::LogicalAnd | Operator::LogicalOr => Ok((b.resolve(labels, defines, visiting)? != 0) as u64),
+                    _ => op.apply(a, b.resolve(labels, defines, visiting)?),
                 }
+            }
+        }
+    }
+
+    /// Resolves a value for floating point data and immediates. Addition, subtraction, multiplication, division
+    /// and negation are done in floating point, everything else is resolved as an integer and converted
+    pub fn resolve_float(&self, labels: &HashMap<String, u32>, defines: &HashMap<String, Value>) -> Result<f64, Log> {
+        self.float(labels, defines, &mut Vec::new())
+    }
+
+    fn float(&self, labels: &HashMap<String, u32>, defines: &HashMap<String, Value>, visiting: &mut Vec<String>) -> Result<f64, Log> {
+        match self {
+            Value::Float(num) => Ok(*num),
+
+            Value::Label(name) if !labels.contains_key(name) && defines.contains_key(name) => float_define(name, labels, defines, visiting),
+            Value::Define(name) => float_define(name, labels, defines, visiting),
+
+            Value::Unary(UnaryOperator::Neg, value) => Ok(-value.float(labels, defines, visiting)?),
+
+            Value::Binary(op @ (Operator::Add | Operator::Sub | Operator::Mul | Operator::Div), a, b) => {
+                let (a, b) = (a.float(labels, defines, visiting)?, b.float(labels, defines, visiting)?);
+
+                Ok(match op {
+                    Operator::Add => a + b,
+                    Operator::Sub => a - b,
+                    Operator::Mul => a * b,
+                    _ => a / b,
+                })
+            }
+
+            _ => Ok(self.resolve(labels, defines, visiting)? as i64 as f64),
+        }
+    }
+
+    /// True if the value is an address, a label plus or minus a constant.
+    /// The difference of two labels is a plain number, and so are `!` defines
+    pub fn is_address(&self) -> bool {
+        self.label_count() == 1
+    }
+
+    /// Number of labels added into the value, subtracted labels counting as -1
+    fn label_count(&self) -> i64 {
+        match self {
+            Value::Label(_) => 1,
+            Value::Unary(UnaryOperator::Neg, value) => -value.label_count(),
+            Value::Binary(Operator::Add, a, b) => a.label_count() + b.label_count(),
+            Value::Binary(Operator::Sub, a, b) => a.label_count() - b.label_count(),
+            _ => 0,
+        }
+    }
+
+    pub fn new(token: &str, last_label: &str) -> Result<Value, Log> {
+        if token.trim().is_empty() {
+            return Err(Log::MissingValue);
+        }
+
+        let mut parser = Parser {
+            text: token.trim(),
+            pos: 0,
+            last_label,
+        };
+
+        let value = parser.expression(0)?;
+        parser.skip_whitespace();
+
+        match parser.pos == parser.text.len() {
+            true  => Ok(value),
+            false => Err(parser.invalid()),
+        }
+    }
+}
+
+/// Resolves a define, `visiting` holds the defines currently being resolved to catch definitions that refer to themselves
+fn resolve_define(name: &str, labels: &HashMap<String, u32>, defines: &HashMap<String, Value>, visiting: &mut Vec<String>) -> Result<u64, Log> {
+    let Some(value) = defines.get(name) else {
+        return Err(Log::NoDefine);
+    };
+
+    if visiting.iter().any(|v| v == name) {
+        return Err(Log::CircularDefine(name.to_string()));
+    }
+
+    visiting.push(name.to_string());
+    let result = value.resolve(labels, defines, visiting);
+    visiting.pop();
+
+    result
+}
+
+/// Same as `resolve_define`, for floating point values
+fn float_define(name: &str, labels: &HashMap<String, u32>, defines: &HashMap<String, Value>, visiting: &mut Vec<String>) -> Result<f64, Log> {
+    let Some(value) = defines.get(name) else {
+        return Err(Log::NoDefine);
+    };
+
+    if visiting.iter().any(|v| v == name) {
+        return Err(Log::CircularDefine(name.to_string()));
+    }
 
-                Ok(values2[0].resolve_value(labels, defines)?)
+    visiting.push(name.to_string());
+    let result = value.float(labels, defines, visiting);
+    visiting.pop();
+
+    result
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    last_label: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn invalid(&self) -> Log {
+        Log::InvalidExpression(self.text.to_string())
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos ..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.pos = self.text.len() - self.rest().trim_start().len();
+    }
+
+    /// Binary operators and their precedence are handled by precedence climbing,
+    /// only operators binding tighter than `min` are taken
+    fn expression(&mut self, min: u8) -> Result<Value, Log> {
+        let mut value = self.unary()?;
+
+        loop {
+            self.skip_whitespace();
+
+            let Some((op, len)) = self.operator() else {
+                break;
+            };
+
+            if op.precedence() <= min {
+                break;
             }
 
-            _ => todo!(),
+            self.pos += len;
+            let rhs = self.expression(op.precedence())?;
+            value = Value::Binary(op, Box::new(value), Box::new(rhs));
         }
+
+        Ok(value)
     }
 
-    pub fn new(token: &str, last_label: &str) -> Value {
-        //split strings on operators / other
-        let substrings: Vec<&str> = token.split_inclusive(['+', '-']).collect();
-        let mut proper_substrings = Vec::new();
+    fn operator(&self) -> Option<(Operator, usize)> {
+        use Operator::*;
 
-        //split out operators into their own strings
-        for &substring in &substrings[0 .. substrings.len() - 1] {
-            proper_substrings.push(substring[0 .. substring.len() - 1].trim());
-            proper_substrings.push(&substring[substring.len() - 1 ..]);
+        let rest = self.rest();
+
+        let two = match rest.get(.. 2) {
+            Some("<<") => Some(Shl),
+            Some(">>") => Some(Shr),
+            Some("<=") => Some(LessEqual),
+            Some(">=") => Some(GreaterEqual),
+            Some("==") => Some(Equal),
+            Some("!=" | "<>") => Some(NotEqual),
+            Some("&&") => Some(LogicalAnd),
+            Some("||") => Some(LogicalOr),
+            _ => None,
+        };
+
+        if let Some(op) = two {
+            return Some((op, 2));
         }
 
-        proper_substrings.push(substrings[substrings.len() - 1].trim());
-        proper_substrings.retain(|&s| !s.is_empty());
-
-        // println!("{:?}", proper_substrings);
-
-        let mut values = Vec::new();
-        for &sub in &proper_substrings {
-            values.push( match sub {
-                "+" => Value::Operator(Operator::Add),
-                "-" => Value::Operator(Operator::Sub),
-                
-                _ => {
-                    match parse_n(sub) {
-                        Ok(number) => Value::Number(number),
-                
-                        Err(_) => if let Some(define) = sub.strip_prefix('!') {
-                            Value::Define(define.to_string())
-                        } else if sub.starts_with('.') {
-                            let mut sub_label = last_label.to_string();
-                            sub_label.push_str(sub);
-                            Value::Label(sub_label)
-                        } else {
-                            Value::Label(sub.to_string())
-                        }
+        let one = match rest.chars().next()? {
+            '*' => Mul,
+            '/' => Div,
+            '%' => Mod,
+            '+' => Add,
+            '-' => Sub,
+            '<' => Less,
+            '>' => Greater,
+            '&' => And,
+            '^' => Xor,
+            '|' => Or,
+            _ => return None,
+        };
+
+        Some((one, 1))
+    }
+
+    fn unary(&mut self) -> Result<Value, Log> {
+        self.skip_whitespace();
+
+        let op = match self.rest().chars().next() {
+            Some('-') => UnaryOperator::Neg,
+            Some('~') => UnaryOperator::Not,
+
+            // !name is a define
+            Some('!') if !self.rest()[1 ..].starts_with(is_name_start) => UnaryOperator::LogicalNot,
+
+            Some('+') => {
+                self.pos += 1;
+                return self.unary();
+            }
+
+            _ => return self.primary(),
+        };
+
+        self.pos += 1;
+        Ok(Value::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Value, Log> {
+        let rest = self.rest();
+
+        match rest.chars().next() {
+            Some('(') => {
+                self.pos += 1;
+                let value = self.expression(0)?;
+                self.skip_whitespace();
+
+                match self.rest().starts_with(')') {
+                    true => {
+                        self.pos += 1;
+                        Ok(value)
                     }
+
+                    false => Err(self.invalid()),
+                }
+            }
+
+            Some('!') => {
+                let name = self.take_name(1);
+                Ok(Value::Define(name.to_string()))
+            }
+
+            Some(c) if c.is_ascii_digit() => {
+                let number = self.take_name(0);
+
+                match parse_n(number) {
+                    Ok(number) => Ok(Value::Number(number)),
+                    Err(err) => self.float(number).ok_or(err),
                 }
-            })
+            }
+
+            // % is only binary where a value is expected, otherwise it's the remainder operator
+            Some(prefix @ ('$' | '%' | '@')) => {
+                let digits = self.take_name(1);
+                Ok(Value::Number(parse_n(&format!("{prefix}{digits}"))?))
+            }
+
+            Some('\'') => {
+                let len = match closing_quote(rest) {
+                    Some(end) => end + 1,
+                    None => return Err(self.invalid()),
+                };
+
+                self.pos += len;
+                Ok(Value::Number(parse_n(&rest[.. len])?))
+            }
+
+            Some(c) if is_name_start(c) => {
+                let name = self.take_name(0);
+
+                match name.starts_with('.') { // sub label
+                    true  => Ok(Value::Label(format!("{}{name}", self.last_label))),
+                    false => Ok(Value::Label(name.to_string())),
+                }
+            }
+
+            _ => Err(self.invalid()),
         }
+    }
+
+    /// Decimal numbers with a fraction or exponent, like 1.5, 2e10 or 1.5e-3
+    fn float(&mut self, number: &str) -> Option<Value> {
+        if !number.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E')) {
+            return None;
+        }
+
+        // the exponent sign ends the name, so take it and the digits after it
+        let mut len = number.len();
 
-        if values.len() == 1 {
-            values[0].to_owned()
-        } else {
-            Value::Expression(values)
+        if number.ends_with(['e', 'E']) && self.rest().starts_with(['+', '-']) {
+            len += 1 + self.rest()[1 ..].find(|c: char| !c.is_ascii_digit()).unwrap_or(self.rest().len() - 1);
         }
+
+        let start = self.pos - number.len();
+        let value = self.text[start .. start + len].parse().ok()?;
+
+        self.pos = start + len;
+        Some(Value::Float(value))
     }
+
+    /// Takes a name or number, skipping `prefix` bytes first
+    fn take_name(&mut self, prefix: usize) -> &'a str {
+        let text = self.text;
+        let start = self.pos + prefix;
+        let len = text[start ..].find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.')).unwrap_or(text.len() - start);
+
+        self.pos = start + len;
+        &text[start .. start + len]
+    }
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '.'
 }
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
 #[derive(Parser)]
 pub struct Args {
@@ -10,7 +10,67 @@ pub struct Args {
     #[arg(short)]
     pub out_file: Option<String>,
 
-    /// Valid options are "M68000", "M68010", "M68020"
+    /// Valid options are "M68000", "M68010", "M68020", "M68030", "M68040", "M68060", "CPU32", and "ISA_A", "ISA_B", "ISA_C" for ColdFire
     #[arg(short, default_value = "M68000")]
     pub target_cpu: String,
+
+    /// Enable 68881/68882 FPU instructions. Requires a 68020 or later, except CPU32
+    #[arg(long)]
+    pub fpu: bool,
+
+    /// Additional directory to search for included files. Can be specified multiple times
+    #[arg(short = 'I')]
+    pub include_paths: Vec<String>,
+
+    /// Path to where to write a listing of each line with its address and encoding, followed by all labels and defines
+    #[arg(short)]
+    pub listing: Option<String>,
+
+    /// Path to where to write the labels and defines
+    #[arg(short)]
+    pub symbols: Option<String>,
+
+    /// Format of the symbol file
+    #[arg(long, value_enum, default_value_t = SymbolFormat::Nm)]
+    pub symbol_format: SymbolFormat,
+
+    /// Format of the assembled file
+    #[arg(short, long, value_enum, default_value_t = Format::Binary)]
+    pub format: Format,
+
+    /// Maximum number of data bytes per record in the S-record and Intel HEX formats
+    #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u8).range(1..=250))]
+    pub record_length: u8,
+
+    /// When to color error and warning messages
+    #[arg(long, value_enum, default_value_t = Color::Auto)]
+    pub color: Color,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// Raw big-endian binary, gaps between orgs are padded with zeroes
+    Binary,
+    /// Motorola S-records, S19, S28 or S37 depending on the highest address
+    Srec,
+    /// Intel HEX, gaps between orgs are left out
+    Ihex,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SymbolFormat {
+    /// "address T name" lines like nm prints, defines are marked A
+    Nm,
+    /// MAME debugger script adding comments at each label
+    Mame,
+    /// Symbol file for Hatari's debugger
+    Hatari,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Color {
+    /// Color when printing to a terminal
+    Auto,
+    Always,
+    Never,
 }
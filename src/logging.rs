@@ -1,6 +1,106 @@
-// struct Logging {
-//     warn: Vec<(u32, LogWarn)>,
-// }
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub log: Log,
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub source: String, // the source line, empty if there is none
+    pub span: Option<(u32, u32)>, // column and length of the part of the source line to underline
+    pub notes: Vec<String>,
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+impl Diagnostic {
+    pub fn new(severity: Severity, log: Log, file: &str, line: u32, column: u32) -> Self {
+        Self {
+            severity,
+            log,
+            file: file.to_string(),
+            line,
+            column,
+            source: String::new(),
+            span: None,
+            notes: Vec::new(),
+        }
+    }
+
+    fn severity_name(&self) -> &'static str {
+        match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    /// Renders the diagnostic in the style of rustc, with the source line and the failing part underlined
+    pub fn render(&self, color: bool) -> String {
+        let paint = |style: &'static str| if color { style } else { "" };
+        let (reset, bold, blue) = (paint(RESET), paint(BOLD), paint(BLUE));
+
+        let accent = match self.severity {
+            Severity::Error => paint(RED),
+            Severity::Warning => paint(YELLOW),
+        };
+
+        let mut out = format!("{accent}{}{reset}{bold}: {}{reset}\n", self.severity_name(), self.log.print());
+
+        if self.line == 0 { //not tied to any source
+            return out;
+        }
+
+        let (column, length) = self.span.unwrap_or((self.column, 1));
+        let location = match self.file.is_empty() {
+            true  => format!("{}:{column}", self.line),
+            false => format!("{}:{}:{column}", self.file, self.line),
+        };
+
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        out.push_str(&format!("{gutter}{blue}-->{reset} {location}\n"));
+
+        if !self.source.is_empty() {
+            // keep tabs so the underline lines up with the source however tabs are displayed
+            let indent: String = self.source.get(.. (column as usize).saturating_sub(1)).unwrap_or("").chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+
+            let underline = "^".repeat(length.max(1) as usize);
+
+            out.push_str(&format!("{gutter} {blue}|{reset}\n"));
+            out.push_str(&format!("{blue}{line_number} |{reset} {}\n", self.source));
+            out.push_str(&format!("{gutter} {blue}|{reset} {indent}{accent}{underline}{reset}\n"));
+        }
+
+        for note in &self.notes {
+            out.push_str(&format!("{gutter} {blue}={reset} {bold}note{reset}: {note}\n"));
+        }
+
+        out
+    }
+}
+
+#[derive(Default)]
+pub struct Logging {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Logging {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+}
 
 #[derive(Debug, PartialEq)]
 pub enum Log {
@@ -8,37 +108,174 @@ pub enum Log {
     InvalidSuffix,
     AnB,
     UnsupportedSuffix,
-    IndexRegisterInvalidSize,
+    IndexRegisterInvalidSize(String),
     InvalidNumber,
-    NoLabel,
+    NoLabel(String),
     NoDefine,
-    InvalidRegister,
+    InvalidRegister(String),
     InvalidAddressingMode,
     TooManyOperands,
     LabelRedefinition,
     CpuTypeModeNotValid,
     SizeOperandMismatch,
     UnsupportedInstruction,
+    UnsupportedControlRegister,
+    FpuDisabled,
+    UnexpectedFloat,
+    MissingMacroName,
+    MacroRedefinition,
+    UnterminatedMacro,
+    UnexpectedEndm,
+    MacroTooManyArguments,
+    MacroDepthExceeded,
+    UnknownMacroParameter(String),
+    InMacro(String, u32, Box<Log>), // macro name, line in macro body, error
+    InOperand(usize, Box<Log>), // index of the operand, error
+    MissingCondition,
+    UnterminatedIf,
+    UnexpectedElse,
+    UnexpectedEndif,
+    FileNotFound(String),
+    InvalidFileName,
+    IncludeCycle(String),
+    IncbinOutOfRange,
+    MissingAddress,
+    AddressOutOfRange,
+    OddAddress,
+    OrgBackwards,
+    BranchOutOfRange,
+    CpuTypeSizeNotValid,
+    MissingValue,
+    InvalidExpression(String),
+    InvalidDefine(String),
+    MismatchedParentheses(String),
+    UnclosedParenthesis(String),
+    UnopenedParenthesis(String),
+    ExtraOperand(String),
+    ExpectedAddressRegister(String),
+    InvalidBaseRegister(String),
+    InvalidIndexRegister(String),
+    InvalidScale(String),
+    TooManyIndexParts(String),
+    InvalidRegisterList(String),
+    InvalidRegisterRange(String),
+    SpecialRegisterSize(&'static str, char), // register, required size
+    QuickImmediateOutOfRange(i64, i64, i64), // value, min, max
+    DivisionByZero,
+    NumberOverflow(String),
+    UnterminatedString(String),
+    InvalidEscape(String),
+    StringNotBytes,
+    StringTooLong(usize),
+    DefineRedefinition(String),
+    InvalidAlignment(u64),
+    ValueOutOfRange(i64, i64, i64), // value, min, max
+    FloatOutOfRange(&'static str, f64), // size suffix, largest magnitude
+    InvalidArgument(String, &'static str), // argument, expected
+    OddInstructionAddress,
+    CircularDefine(String),
+    MissingBitfield(String),
+
+    AddSubQAddressWord,
+    BitNumberWraps(i64, i64), // bit number, highest bit
 }
 
 impl Log {
-    pub fn print(&self) -> &str {
+    /// The operand or name the error is about, if it carries one
+    pub fn highlight(&self) -> Option<&str> {
+        match self {
+            Self::InvalidRegister(text) | Self::FileNotFound(text) | Self::IncludeCycle(text) |
+            Self::InvalidExpression(text) | Self::MismatchedParentheses(text) | Self::UnclosedParenthesis(text) |
+            Self::UnopenedParenthesis(text) | Self::ExtraOperand(text) | Self::ExpectedAddressRegister(text) |
+            Self::InvalidBaseRegister(text) | Self::InvalidIndexRegister(text) | Self::InvalidScale(text) |
+            Self::TooManyIndexParts(text) | Self::InvalidRegisterList(text) | Self::InvalidRegisterRange(text) |
+            Self::NumberOverflow(text) | Self::UnterminatedString(text) | Self::InvalidEscape(text) |
+            Self::MissingBitfield(text) | Self::IndexRegisterInvalidSize(text) | Self::NoLabel(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Marks the error as coming from an operand, so the operand is underlined
+    pub fn in_operand(self, operand: usize) -> Log {
+        Self::InOperand(operand, Box::new(self))
+    }
+
+    pub fn print(&self) -> String {
         match self {
             Self::InvalidOp => "Invalid opcode",
             Self::InvalidSuffix => "Invalid size suffix",
             Self::AnB => "Byte operations on address registers are invalid",
             Self::UnsupportedSuffix => "This opcode does not support this size",
-            Self::IndexRegisterInvalidSize => "Index register size is either invalid or missing",
+            Self::IndexRegisterInvalidSize(op) => return format!("Index register size is either invalid or missing in \"{op}\""),
             Self::InvalidNumber => "Failed to parse number",
-            Self::NoLabel => "Label doesn't exist",
+            Self::NoLabel(name) => return format!("Label \"{name}\" doesn't exist"),
             Self::NoDefine => "Define doesn't exist",
-            Self::InvalidRegister => "Invalid register specified",
+            Self::InvalidRegister(reg) => return format!("Invalid register \"{reg}\""),
             Self::InvalidAddressingMode => "Invalid addressing mode",
             Self::TooManyOperands => "Expected one operand, found two",
             Self::LabelRedefinition => "Label redefinition",
             Self::CpuTypeModeNotValid => "This addressing mode is not valid for this CPU type",
             Self::SizeOperandMismatch => "invalid size / operand combination",
             Self::UnsupportedInstruction => "Target CPU does not support this instruction",
-        }
+            Self::UnsupportedControlRegister => "Target CPU does not have this control register",
+            Self::FpuDisabled => "FPU instructions need the --fpu flag",
+            Self::UnexpectedFloat => "Floating point value where an integer is expected",
+            Self::MissingMacroName => "Macro definition is missing a name",
+            Self::MacroRedefinition => "Macro redefinition",
+            Self::UnterminatedMacro => "Macro definition is missing endm",
+            Self::UnexpectedEndm => "endm without a matching macro",
+            Self::MacroTooManyArguments => "Too many arguments passed to macro",
+            Self::MacroDepthExceeded => "Macro expansion nested too deeply",
+            Self::UnknownMacroParameter(name) => return format!("Unknown macro parameter \"\\{name}\""),
+            Self::MissingCondition => "Missing condition after if",
+            Self::UnterminatedIf => "Conditional block is missing endif",
+            Self::UnexpectedElse => "else/elseif without a matching if, or after else",
+            Self::UnexpectedEndif => "endif without a matching if",
+            Self::FileNotFound(name) => return format!("File \"{name}\" not found"),
+            Self::InvalidFileName => "Expected a file name in quotes",
+            Self::IncludeCycle(name) => return format!("File \"{name}\" includes itself"),
+            Self::IncbinOutOfRange => "Offset or length is outside of the included file",
+            Self::MissingAddress => "Expected an address",
+            Self::AddressOutOfRange => "Address does not fit in 32 bits",
+            Self::OddAddress => "Address must be even",
+            Self::OrgBackwards => "org address is below the current position in the output",
+            Self::BranchOutOfRange => "Branch target is out of range for this size",
+            Self::CpuTypeSizeNotValid => "This size is not valid for this CPU type",
+            Self::MissingValue => "Expected a value",
+            Self::InvalidExpression(expr) => return format!("Invalid expression \"{expr}\""),
+            Self::InvalidDefine(text) => return format!("Invalid define \"{text}\", expected \"!name = value\""),
+            Self::MismatchedParentheses(text) => return format!("Mismatched parentheses in \"{text}\""),
+            Self::UnclosedParenthesis(op) => return format!("Missing closing parenthesis in \"{op}\""),
+            Self::UnopenedParenthesis(op) => return format!("Missing opening parenthesis in \"{op}\""),
+            Self::ExtraOperand(op) => return format!("Unexpected extra operand \"{op}\""),
+            Self::ExpectedAddressRegister(op) => return format!("Expected an address register in \"{op}\""),
+            Self::InvalidBaseRegister(op) => return format!("Expected an address register or PC as base register in \"{op}\""),
+            Self::InvalidIndexRegister(op) => return format!("Expected a data or address register as index register in \"{op}\""),
+            Self::InvalidScale(op) => return format!("Invalid index scale in \"{op}\", valid scales are 1, 2, 4 and 8"),
+            Self::TooManyIndexParts(op) => return format!("Too many comma separated parts in \"{op}\""),
+            Self::InvalidRegisterList(list) => return format!("Invalid register list \"{list}\""),
+            Self::InvalidRegisterRange(range) => return format!("Register range \"{range}\" mixes data and address registers"),
+            Self::SpecialRegisterSize(reg, size) => return format!("This instruction requires size .{size} when used with {reg}"),
+            Self::QuickImmediateOutOfRange(val, min, max) => return format!("Immediate value {val} is out of range, expected {min} to {max}"),
+            Self::DivisionByZero => "Division by zero",
+            Self::NumberOverflow(number) => return format!("Number \"{number}\" does not fit in 64 bits"),
+            Self::UnterminatedString(text) => return format!("Missing closing quote in {text}"),
+            Self::InvalidEscape(text) => return format!("Invalid escape sequence in {text}"),
+            Self::StringNotBytes => "Strings can only be used in byte data",
+            Self::DefineRedefinition(name) => return format!("\"{name}\" is already defined, use set to define values that change"),
+            Self::InvalidAlignment(align) => return format!("Invalid alignment {align}"),
+            Self::ValueOutOfRange(val, min, max) => return format!("Value {val} is out of range, expected {min} to {max}"),
+            Self::FloatOutOfRange(size, max) => return format!("Float value is out of range for {size}, the largest magnitude is {max:e}"),
+            Self::InvalidArgument(arg, expected) => return format!("Invalid argument \"{arg}\", expected {expected}"),
+            Self::CircularDefine(name) => return format!("Define \"{name}\" refers to itself"),
+            Self::MissingBitfield(op) => return format!("Expected a bitfield like {{offset:width}} after \"{op}\""),
+            Self::OddInstructionAddress => "Instruction is at an odd address, use even or align before it",
+            Self::StringTooLong(len) => return format!("String is {len} bytes long, a length prefixed string can be at most 255 bytes"),
+
+            Self::BitNumberWraps(bit, max) => return format!("Bit number {bit} is above {max} and wraps around to {}", bit % (max + 1)),
+            Self::AddSubQAddressWord => "addq.w/subq.w will operate on the entire address register",
+            Self::InMacro(name, line, e) => return format!("In macro \"{name}\", line {line}: {}", e.print()),
+            Self::InOperand(_, e) => return e.print(),
+        }.to_string()
     }
 }
@@ -1,57 +1,103 @@
+use std::io::IsTerminal;
+
 use assembler::CpuType;
 use clap::Parser;
-use cli_parse::Args;
+use cli_parse::{Args, Color, Format, SymbolFormat};
 
 mod assembler;
 mod logging;
+#[allow(clippy::module_inception)]
 mod tests;
 mod cli_parse;
+mod output;
 
 fn main() {
     let args = Args::parse();
 
+    let name = match args.in_file.rsplit_once('.') {
+        Some((file_name, _)) => file_name,
+        None => &args.in_file,
+    };
+
     let out_file = match args.out_file {
         Some(s) => s,
 
-        None => {
-            let name = match args.in_file.rsplit_once('.') {
-                Some((file_name, _)) => file_name,
-                None => &args.in_file,
-            };
-    
-            format!("{name}.bin")
-        }
+        None => match args.format {
+            Format::Binary => format!("{name}.bin"),
+            Format::Srec => format!("{name}.srec"),
+            Format::Ihex => format!("{name}.hex"),
+        },
     };
 
     let target_cpu = match args.target_cpu.as_str() {
         "M68000" => CpuType::MC68000,
         "M68010" => CpuType::MC68010,
         "M68020" => CpuType::MC68020,
+        "M68030" => CpuType::MC68030,
+        "M68040" => CpuType::MC68040,
+        "M68060" => CpuType::MC68060,
+        "CPU32"  => CpuType::CPU32,
+        "ISA_A"  => CpuType::ColdFireA,
+        "ISA_B"  => CpuType::ColdFireB,
+        "ISA_C"  => CpuType::ColdFireC,
 
         _ => {
-            println!("Invalid cpu type specified");
-            return;
+            eprintln!("Invalid cpu type specified");
+            std::process::exit(1);
         }
     };
 
+    let color = match args.color {
+        Color::Auto => std::io::stderr().is_terminal(),
+        Color::Always => true,
+        Color::Never => false,
+    };
+
     let mut asm = assembler::Assembler::default();
     asm.cpu_type = target_cpu;
+    asm.fpu = args.fpu;
+    asm.include_paths = args.include_paths.iter().map(|p| p.into()).collect();
+
+    let assembled = match asm.run_file(&args.in_file) {
+        Ok(assembled) => assembled.clone(),
 
-    let text = std::fs::read_to_string(args.in_file).expect("couldn't read file");
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{}", diagnostic.render(color));
+            }
 
-    match asm.run(&text) {
-        Ok(assembled) => {
-            println!("{:04X?}", assembled);
+            std::process::exit(1);
+        }
+    };
 
-            let mut u8_vec = Vec::new();
+    for warning in &asm.logging.diagnostics {
+        eprintln!("{}", warning.render(color));
+    }
 
-            for elem in assembled {
-                u8_vec.extend(elem.to_be_bytes());
-            }
+    let output = match args.format {
+        Format::Binary => assembled.iter().flat_map(|word| word.to_be_bytes()).collect(),
 
-            std::fs::write(out_file, u8_vec).expect("unable to write file");
+        Format::Srec => {
+            let header = name.rsplit(['/', '\\']).next().unwrap_or("");
+            output::srecord(&asm.segments, asm.start, args.record_length as usize, header).into_bytes()
         }
 
-        Err((err_type, l)) => println!("Line {}: {}", l, err_type.print()),
+        Format::Ihex => output::intel_hex(&asm.segments, asm.start, args.record_length as usize).into_bytes(),
+    };
+
+    std::fs::write(out_file, output).expect("unable to write file");
+
+    if let Some(listing) = args.listing {
+        std::fs::write(listing, asm.listing()).expect("unable to write listing");
+    }
+
+    if let Some(symbols) = args.symbols {
+        let text = match args.symbol_format {
+            SymbolFormat::Nm => output::nm_symbols(&asm.symbols(), &asm.define_values()),
+            SymbolFormat::Mame => output::mame_script(&asm.symbols()),
+            SymbolFormat::Hatari => output::hatari_symbols(&asm.symbols()),
+        };
+
+        std::fs::write(symbols, text).expect("unable to write symbol file");
     }
 }
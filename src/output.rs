@@ -0,0 +1,114 @@
+/// Splits the segments into records of at most `record_len` bytes, as (address, data)
+fn records(segments: &[(u32, Vec<u8>)], record_len: usize) -> impl Iterator<Item = (u32, &[u8])> {
+    segments.iter().flat_map(move |(addr, bytes)| {
+        bytes.chunks(record_len).enumerate().map(move |(idx, chunk)| (addr + (idx * record_len) as u32, chunk))
+    })
+}
+
+/// Motorola S-records. Uses S1/S2/S3 data records depending on the highest address,
+/// followed by an S5/S6 record count and an S9/S8/S7 start address
+pub fn srecord(segments: &[(u32, Vec<u8>)], start: Option<u32>, record_len: usize, header: &str) -> String {
+    let highest = segments.iter()
+        .map(|(addr, bytes)| addr + bytes.len() as u32 - 1)
+        .chain(start)
+        .max()
+        .unwrap_or(0);
+
+    let (data_type, end_type, addr_len) = match highest {
+        0 ..= 0xFFFF => (1, 9, 2),
+        0x1_0000 ..= 0xFF_FFFF => (2, 8, 3),
+        _ => (3, 7, 4),
+    };
+
+    let mut out = srecord_line(0, 0, 2, header.as_bytes());
+    let mut count = 0;
+
+    for (addr, data) in records(segments, record_len) {
+        out.push_str(&srecord_line(data_type, addr, addr_len, data));
+        count += 1;
+    }
+
+    match count {
+        0 ..= 0xFFFF => out.push_str(&srecord_line(5, count, 2, &[])),
+        _ => out.push_str(&srecord_line(6, count, 3, &[])),
+    }
+
+    out.push_str(&srecord_line(end_type, start.unwrap_or(0), addr_len, &[]));
+    out
+}
+
+fn srecord_line(record_type: u8, addr: u32, addr_len: usize, data: &[u8]) -> String {
+    let mut bytes = vec![(addr_len + data.len() + 1) as u8];
+    bytes.extend(&addr.to_be_bytes()[4 - addr_len ..]);
+    bytes.extend(data);
+
+    let checksum = !bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    bytes.push(checksum);
+
+    let hex: String = bytes.iter().map(|b| format!("{b:02X}")).collect();
+    format!("S{record_type}{hex}\n")
+}
+
+/// Intel HEX. Extended linear address records are added whenever the upper 16 bits of the address change,
+/// and the start address from `end` becomes a start linear address record
+pub fn intel_hex(segments: &[(u32, Vec<u8>)], start: Option<u32>, record_len: usize) -> String {
+    let mut out = String::new();
+    let mut upper = 0;
+
+    for (addr, bytes) in segments {
+        let mut pos = 0;
+
+        while pos < bytes.len() {
+            let addr = addr + pos as u32;
+
+            if addr >> 16 != upper {
+                upper = addr >> 16;
+                out.push_str(&intel_hex_line(4, 0, &(upper as u16).to_be_bytes()));
+            }
+
+            // records can't cross into the next 64KiB
+            let len = record_len.min(bytes.len() - pos).min(0x1_0000 - (addr & 0xFFFF) as usize);
+
+            out.push_str(&intel_hex_line(0, addr as u16, &bytes[pos .. pos + len]));
+            pos += len;
+        }
+    }
+
+    if let Some(start) = start {
+        out.push_str(&intel_hex_line(5, 0, &start.to_be_bytes()));
+    }
+
+    out.push_str(&intel_hex_line(1, 0, &[]));
+    out
+}
+
+fn intel_hex_line(record_type: u8, addr: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend(addr.to_be_bytes());
+    bytes.push(record_type);
+    bytes.extend(data);
+
+    let checksum = bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)).wrapping_neg();
+    bytes.push(checksum);
+
+    let hex: String = bytes.iter().map(|b| format!("{b:02X}")).collect();
+    format!(":{hex}\n")
+}
+
+/// Symbols in the style of `nm`, labels as text symbols and defines as absolute symbols
+pub fn nm_symbols(labels: &[(&str, u32)], defines: &[(&str, u64)]) -> String {
+    let labels = labels.iter().map(|(name, addr)| format!("{addr:08X} T {name}\n"));
+    let defines = defines.iter().map(|(name, value)| format!("{value:08X} A {name}\n"));
+
+    labels.chain(defines).collect()
+}
+
+/// MAME debugger script adding a comment with the label name at each label, run with `source <file>`
+pub fn mame_script(labels: &[(&str, u32)]) -> String {
+    labels.iter().map(|(name, addr)| format!("comadd {addr:X},{name}\n")).collect()
+}
+
+/// Symbol file for Hatari's `symbols` command. Only addresses can be loaded, so defines are left out
+pub fn hatari_symbols(labels: &[(&str, u32)]) -> String {
+    labels.iter().map(|(name, addr)| format!("0x{addr:08X} T {name}\n")).collect()
+}
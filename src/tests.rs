@@ -1,9 +1,23 @@
 #[cfg(test)]
 mod tests {
     use crate::*;
+    use logging::{Log, Severity};
+
+    /// Runs the assembler, returning errors as (error, line) pairs
+    fn errors(asm: &mut assembler::Assembler, text: &str) -> Vec<(Log, u32)> {
+        match asm.run(text) {
+            Ok(_) => vec![],
+
+            Err(diagnostics) => diagnostics
+                .into_iter()
+                .filter(|d| d.severity == Severity::Error)
+                .map(|d| (d.log, d.line))
+                .collect(),
+        }
+    }
 
     #[test]
-    fn no_operands() -> Result<(), (logging::Log, u32)> {
+    fn no_operands() -> Result<(), Vec<logging::Diagnostic>> {
         let data = [
             ("illegal", vec![0b0100101011111100]),
             ("nop",     vec![0b0100111001110001]),
@@ -21,4 +35,1241 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn macros() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+        macro clear reg
+            moveq #0, \\reg
+        endm
+
+        macro clear_two
+            clear \\1
+            clear \\2
+        endm
+
+        macro wait
+        .loop\\@:
+            dbf \\1, .loop\\@
+        endm
+
+        macro copy src, dst
+            move.\\0 \\src, \\dst
+        endm
+
+        start:
+            clear_two D1, D2
+            wait D0
+            wait D0
+            copy.w dst=D4, src=D3
+    ";
+
+        let expected = vec![0x7200, 0x7400, 0x51C8, 0xFFFE, 0x51C8, 0xFFFE, 0x3803];
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(asm.run(text)?, &expected);
+
+        let mut asm = assembler::Assembler::default();
+        let text = "macro bad\n  move.q D0, D1\nendm\n\nbad";
+        assert_eq!(errors(&mut asm, text), vec![(Log::InMacro("bad".into(), 2, Box::new(Log::InvalidSuffix)), 5)]);
+
+        let mut asm = assembler::Assembler::default();
+        let text = "macro m\n  nop\nendm\nmacro m\n  rts\nendm\n  m";
+        assert_eq!(errors(&mut asm, text), vec![(Log::MacroRedefinition, 4)]);
+
+        let mut asm = assembler::Assembler::default();
+        let text = "macro m\n  m\nendm\n  m";
+        assert_eq!(errors(&mut asm, text), vec![(Log::MacroDepthExceeded, 4)]);
+
+        let mut asm = assembler::Assembler::default();
+        let text = "macro m\n  move.w \\b, D0\nendm\n  m D1";
+        assert_eq!(errors(&mut asm, text), vec![(Log::UnknownMacroParameter("b".into()), 4)]);
+
+        // escape sequences in strings are left alone
+        let mut asm = assembler::Assembler::default();
+        let text = "macro m\n  dc.b \"\\n\\\"\\t\", \\1\nendm\n  m 0";
+        assert_eq!(asm.run(text)?, &vec![0x0A22, 0x0900]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn conditionals() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+        !target = 2
+        !debug = 1
+
+        if !target - 1
+            ifdef !debug
+                nop
+            else
+                rts
+            endif
+        elseif 1
+            illegal
+        else
+            illegal
+        endif
+
+        ifndef !debug
+            illegal
+        endif
+
+        macro count
+            if \\1
+                nop
+                count \\1 - 1
+            endif
+        endm
+
+        count 2
+    ";
+
+        let expected = vec![0x4E71, 0x4E71, 0x4E71];
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(asm.run(text)?, &expected);
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(errors(&mut asm, "if 1\nnop"), vec![(Log::UnterminatedIf, 2)]);
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(errors(&mut asm, "if 1\nelse\nelse\nendif"), vec![(Log::UnexpectedElse, 3)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn include() -> Result<(), Vec<logging::Diagnostic>> {
+        let dir = std::env::temp_dir().join("mega68_include_test");
+        std::fs::create_dir_all(dir.join("inc")).unwrap();
+        std::fs::write(dir.join("inc/sub.asm"), "nop\nincbin \"data.bin\", 1, 3\n").unwrap();
+        std::fs::write(dir.join("inc/data.bin"), [0x11, 0x22, 0x33, 0x44, 0x55]).unwrap();
+        std::fs::write(dir.join("inc/bad.asm"), "nop\nmove.q D0, D1\n").unwrap();
+        std::fs::write(dir.join("inc/cycle.asm"), "include \"cycle.asm\"\n").unwrap();
+        std::fs::write(dir.join("inc/outer.asm"), "include \"bad.asm\"\n").unwrap();
+
+        let mut asm = assembler::Assembler::default();
+        asm.include_paths.push(dir.join("inc"));
+        assert_eq!(asm.run("include \"sub.asm\"\nrts")?, &vec![0x4E71, 0x2233, 0x4400, 0x4E75]);
+
+        let mut asm = assembler::Assembler::default();
+        asm.include_paths.push(dir.join("inc"));
+        let diagnostics = asm.run("nop\ninclude \"bad.asm\"").unwrap_err();
+        assert_eq!(diagnostics[0].file, dir.join("inc/bad.asm").display().to_string());
+        assert_eq!((&diagnostics[0].log, diagnostics[0].line), (&Log::InvalidSuffix, 2));
+        assert_eq!(diagnostics[0].notes[1 ..], ["included from line 2".to_string()]);
+
+        let mut asm = assembler::Assembler::default();
+        asm.include_paths.push(dir.join("inc"));
+        let diagnostics = asm.run("include \"outer.asm\"").unwrap_err();
+        assert_eq!(diagnostics[0].notes[1 ..], [
+            format!("included from {}:1", dir.join("inc/outer.asm").display()),
+            "included from line 1".to_string(),
+        ]);
+
+        let mut asm = assembler::Assembler::default();
+        asm.include_paths.push(dir.join("inc"));
+        assert_eq!(errors(&mut asm, "include \"cycle.asm\""), vec![(Log::IncludeCycle("cycle.asm".into()), 1)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn origin() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+        org 4
+    start:
+        bra.w start
+        lea (data, PC), A0
+    data:
+        d16 data
+        rorg 0xFF0000
+    ram:
+        jmp ram
+    ";
+
+        let expected = vec![0, 0, 0x6000, 0xFFFE, 0x41FA, 0x0002, 0x000C, 0x4EF9, 0x00FF, 0x0000];
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(asm.run(text)?, &expected);
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(errors(&mut asm, "org 4\nnop\norg 2"), vec![(Log::OrgBackwards, 3)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn branch_relaxation() -> Result<(), Vec<logging::Diagnostic>> {
+        let mut text = String::from("start:\nbra end\nbne.s start\nbeq next\nnext:\n");
+        text.push_str(&"nop\n".repeat(200));
+        text.push_str("end:\nbra start\n");
+
+        let mut expected = vec![0x6000, 0x0198, 0x66FA, 0x6700, 0x0002];
+        expected.extend(vec![0x4E71; 200]);
+        expected.extend([0x6000, 0xFE64]);
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(asm.run(&text)?, &expected);
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(errors(&mut asm, &text.replace("bra end", "bra.b end")), vec![(Log::BranchOutOfRange, 2)]);
+
+        let mut asm = assembler::Assembler::default();
+        asm.cpu_type = assembler::CpuType::MC68020;
+        let text = format!("bra.l end\n{}end:", "nop\n".repeat(2));
+        assert_eq!(asm.run(&text)?, &vec![0x60FF, 0x0000, 0x0008, 0x4E71, 0x4E71]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn diagnostics() {
+        let text = "
+        nop
+        move.q D0, D1
+        addq.w #1, A0
+        lea (missing, PC), A0
+        rts
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        let diagnostics = asm.run(text).unwrap_err();
+
+        let found: Vec<_> = diagnostics.iter().map(|d| (d.severity, &d.log, d.line, d.column, d.span)).collect();
+
+        assert_eq!(found, vec![
+            (Severity::Error, &Log::InvalidSuffix, 3, 9, Some((14, 1))),
+            (Severity::Warning, &Log::AddSubQAddressWord, 4, 9, Some((9, 6))),
+            (Severity::Error, &Log::NoLabel("missing".into()), 5, 9, Some((14, 7))),
+        ]);
+
+        // range errors underline the operand with the value
+        let text = "
+        moveq #128, D0
+        dc.b 1, 300
+        move.w 70000(A0), D0
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        let diagnostics = asm.run(text).unwrap_err();
+
+        let found: Vec<_> = diagnostics.iter().map(|d| (&d.log, d.line, d.span)).collect();
+
+        assert_eq!(found, vec![
+            (&Log::QuickImmediateOutOfRange(128, -128, 127), 2, Some((15, 4))),
+            (&Log::ValueOutOfRange(300, -128, 255), 3, Some((17, 3))),
+            (&Log::ValueOutOfRange(70000, -0x8000, 0x7FFF), 4, Some((16, 9))),
+        ]);
+    }
+
+    #[test]
+    fn render() {
+        let text = "
+    lea (A0, D9.w), A1
+\tlea.w (A0), A1
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        let diagnostics = asm.run(text).unwrap_err();
+
+        assert_eq!(diagnostics[0].render(false), concat!(
+            "error: Invalid register \"D9\"\n",
+            " --> 2:14\n",
+            "  |\n",
+            "2 |     lea (A0, D9.w), A1\n",
+            "  |              ^^\n",
+        ));
+
+        assert_eq!(diagnostics[1].render(false), concat!(
+            "error: This opcode does not support this size\n",
+            " --> 3:6\n",
+            "  |\n",
+            "3 | \tlea.w (A0), A1\n",
+            "  | \t    ^\n",
+            "  = note: valid sizes for `lea` are: .l\n",
+        ));
+
+        // (An, Xn) is the same as 0(An, Xn)
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(asm.run("lea (A0, D1.w), A1").unwrap(), &vec![0x43F0, 0x1000]);
+    }
+
+    #[test]
+    fn malformed_operands() {
+        let data = [
+            ("move.l (A0, D0", Log::MismatchedParentheses("(A0, D0".into())),
+            ("move.l (A0", Log::MismatchedParentheses("(A0".into())),
+            ("move.l -(A9, D0", Log::MismatchedParentheses("-(A9, D0".into())),
+            ("move.l -(A9), D0", Log::InvalidRegister("A9".into())),
+            ("move.l -(D0), D0", Log::ExpectedAddressRegister("-(D0)".into())),
+            ("move.l (D0, X, Y, Z), D0", Log::TooManyIndexParts("(D0, X, Y, Z)".into())),
+            ("move.l (0, D1, D0.w), D0", Log::InvalidBaseRegister("(0, D1, D0.w)".into())),
+            ("move.l (0, A1, X0.w), D0", Log::InvalidIndexRegister("(0, A1, X0.w)".into())),
+            ("move.l (0, A1, D0.b), D0", Log::IndexRegisterInvalidSize("(0, A1, D0.b)".into())),
+            ("movem.l D0-A3, -(A7)", Log::InvalidRegisterRange("D0-A3".into())),
+            ("movem.l D0/X, -(A7)", Log::InvalidRegisterList("D0/X".into())),
+            ("addq.l #9, D0", Log::QuickImmediateOutOfRange(9, 1, 8)),
+            ("move.b SR, D0", Log::SpecialRegisterSize("SR", 'w')),
+            ("move.l D0, D1, D2", Log::ExtraOperand("D2".into())),
+            ("move.l #1 +, D0", Log::InvalidExpression("1 +".into())),
+            ("move.l #, D0", Log::MissingValue),
+            ("!five", Log::InvalidDefine("!five".into())),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, text), vec![(expected, 1)], "{text}");
+        }
+
+        // malformed input must never panic
+        let fragments = ["(", ")", "-(", ")+", ",", "A", "D", "é", "#", "*", ".", "A9", "PC", "0x", "-", "+", "/"];
+
+        for a in fragments {
+            for b in fragments {
+                let mut asm = assembler::Assembler::default();
+                let _ = asm.run(&format!("move.l {a}{b}, {b}{a}\nmovem.w {a}{b}, -(A7)\nd08 {a}{b}"));
+            }
+        }
+    }
+
+    #[test]
+    fn absolute_sizing() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+        move.w $1234, D0
+        move.w $FFFF8000, D0
+        move.w $8000, D0
+        move.w $10.l, D0
+        jmp target
+        ds.b $7FE8
+    target:
+        nop
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        let words = asm.run(text)?;
+
+        // target is past $7FFF, so the jmp is absolute long
+        assert_eq!(words[.. 12], [0x3038, 0x1234, 0x3038, 0x8000, 0x3039, 0x0000, 0x8000, 0x3039, 0x0000, 0x0010, 0x4EF9, 0x0000]);
+        assert_eq!(words[12], 0x8002);
+
+        Ok(())
+    }
+
+    #[test]
+    fn range_checks() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+        moveq #-128, D0
+        moveq #100, D1
+        move.b #-1, D0
+        move.b #255, D0
+        move.w #$FFFF, D0
+        move.w -32768(A0), D0
+        move.b 127(A0, D0.w), D0
+        lsl.w #8, D0
+        trap #15
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        let words = asm.run(text)?;
+
+        assert_eq!(words, &vec![
+            0x7080,
+            0x7264,
+            0x103C, 0x00FF,
+            0x103C, 0x00FF,
+            0x303C, 0xFFFF,
+            0x3028, 0x8000,
+            0x1030, 0x007F,
+            0xE148,
+            0x4E4F,
+        ]);
+
+        let data = [
+            ("moveq #128, D0", Log::QuickImmediateOutOfRange(128, -128, 127)),
+            ("addq.w #257, D0", Log::QuickImmediateOutOfRange(257, 1, 8)),
+            ("lsr.l #0, D0", Log::QuickImmediateOutOfRange(0, 1, 8)),
+            ("trap #16", Log::QuickImmediateOutOfRange(16, 0, 15)),
+            ("move.b #256, D0", Log::ValueOutOfRange(256, -128, 255)),
+            ("move.w #-32769, D0", Log::ValueOutOfRange(-32769, -32768, 65535)),
+            ("move.l #$100000000, D0", Log::ValueOutOfRange(0x1_0000_0000, -0x8000_0000, 0xFFFF_FFFF)),
+            ("move.w 32768(A0), D0", Log::ValueOutOfRange(32768, -32768, 32767)),
+            ("move.w -129(A0, D0.w), D0", Log::ValueOutOfRange(-129, -128, 127)),
+            ("move.w $8000.w, D0", Log::ValueOutOfRange(0x8000, -0x8000, 0x7FFF)),
+            ("d08 256", Log::ValueOutOfRange(256, -128, 255)),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, text).into_iter().map(|e| e.0).collect::<Vec<_>>(), vec![expected], "{text}");
+        }
+
+        let mut asm = assembler::Assembler::default();
+        asm.run("    btst.b #9, (A0)")?;
+        assert_eq!(asm.logging.diagnostics[0].log, Log::BitNumberWraps(9, 7));
+
+        Ok(())
+    }
+
+    #[test]
+    fn full_extension_words() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+        move.l ([4, A0, D1.w*4], 8), D0
+        move.l ([A0], D1.l, 8), D0
+        move.l (ZA0, D0.w), D0
+        move.w (4, A0, D0.w), D1
+        lea ($12345, A0, D0.w), A1
+        lea ($12345, A0), A1
+        move.l ([$1000.l], D2.w, $7FFF0000), D3
+        move.w ([table, PC]), D0
+        move.l (ZA0), D0
+        move.l (D0.l*4), D1
+    table:
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        asm.cpu_type = assembler::CpuType::MC68020;
+        let words = asm.run(text)?;
+
+        assert_eq!(words, &vec![
+            0x2030, 0x1522, 0x0004, 0x0008,
+            0x2030, 0x1916, 0x0008,
+            0x2030, 0x0190,
+            0x3230, 0x0004,
+            0x43F0, 0x0130, 0x0001, 0x2345,
+            0x43F0, 0x0170, 0x0001, 0x2345,
+            0x2630, 0x21B7, 0x0000, 0x1000, 0x7FFF, 0x0000,
+            0x303B, 0x0161, 0x000C,
+            0x2030, 0x01D0,
+            0x2230, 0x0D90,
+        ]);
+
+        let data = [
+            ("move.l ([A0]), D0", Log::CpuTypeModeNotValid),
+            ("move.l (ZA0, D0.w), D0", Log::CpuTypeModeNotValid),
+            ("move.l (ZA0), D0", Log::CpuTypeModeNotValid),
+            ("move.l (D0.l*4), D0", Log::CpuTypeModeNotValid),
+            ("lea ($12345, A0), A1", Log::ValueOutOfRange(0x12345, -32768, 32767)),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, text).into_iter().map(|e| e.0).collect::<Vec<_>>(), vec![expected], "{text}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn srecord() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+        nop
+        nop
+        nop
+        org 0x10000
+    entry:
+        rts
+        end entry
+        illegal
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        asm.run(text)?;
+
+        assert_eq!(asm.start, Some(0x10000));
+
+        assert_eq!(output::srecord(&asm.segments, asm.start, 4, "hdr"), concat!(
+            "S0060000686472BB\n",
+            "S2080000004E714E7179\n",
+            "S2060000044E7136\n",
+            "S2060100004E7535\n",
+            "S5030003F9\n",
+            "S804010000FA\n",
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn intel_hex() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+        nop
+        nop
+        org 0x1FFFE
+    entry:
+        nop
+        rts
+        end entry
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        asm.run(text)?;
+
+        // the gap isn't filled and the record crossing 0x20000 is split
+        assert_eq!(output::intel_hex(&asm.segments, asm.start, 16), concat!(
+            ":040000004E714E717E\n",
+            ":020000040001F9\n",
+            ":02FFFE004E7142\n",
+            ":020000040002F8\n",
+            ":020000004E753B\n",
+            ":040000050001FFFEF9\n",
+            ":00000001FF\n",
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn listing() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "\
+    macro clear
+    moveq #0, \\1
+    endm
+start:
+    clear D1
+    d16 1, 2, 3, 4, 5
+!two = 2
+size equ 4";
+
+        let mut asm = assembler::Assembler::default();
+        asm.run(text)?;
+
+        assert_eq!(asm.listing(), concat!(
+            "     1 00000000                        macro clear\n",
+            "     2 00000000                            moveq #0, \\1\n",
+            "     3 00000000                            endm\n",
+            "     4 00000000                        start:\n",
+            "     5 00000000                            clear D1\n",
+            "     2 00000000  7200                  + moveq #0, D1\n",
+            "     6 00000002  0001 0002 0003 0004       d16 1, 2, 3, 4, 5\n",
+            "       0000000A  0005\n",
+            "     7 0000000C                        !two = 2\n",
+            "     8 0000000C                        size equ 4\n",
+            "\n",
+            "Labels\n",
+            "00000000  start\n",
+            "\n",
+            "Defines\n",
+            "00000004  size\n",
+            "00000002  !two\n",
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn symbols() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+    !size = 0x20
+    org 0x400
+    main:
+        nop
+    .loop:
+        bra .loop
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        asm.run(text)?;
+
+        assert_eq!(output::nm_symbols(&asm.symbols(), &asm.define_values()), concat!(
+            "00000400 T main\n",
+            "00000402 T main.loop\n",
+            "00000020 A size\n",
+        ));
+
+        assert_eq!(output::mame_script(&asm.symbols()), "comadd 400,main\ncomadd 402,main.loop\n");
+        assert_eq!(output::hatari_symbols(&asm.symbols()), "0x00000400 T main\n0x00000402 T main.loop\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn expressions() -> Result<(), Vec<logging::Diagnostic>> {
+        let data = [
+            ("1 + 2 * 3", 7),
+            ("(1 + 2) * 3", 9),
+            ("10 - 2 - 3", 5),
+            ("-2 * -3", 6),
+            ("+4", 4),
+            ("7 / 2", 3),
+            ("-7 / 2", -3i32 as u32),
+            ("7 % 3", 1),
+            ("1 << 4 | 1", 17),
+            ("0x80 >> 4", 8),
+            ("-16 >> 2", -4i32 as u32),
+            ("6 & 3 ^ 1", 3),
+            ("~0", 0xFFFF_FFFF),
+            ("!0", 1),
+            ("!(5)", 0),
+            ("2 < 3", 1),
+            ("3 <= 2", 0),
+            ("1 == 1 && 2 != 2", 0),
+            ("0 || 5", 1),
+            ("!one * 2 + start", 2),
+        ];
+
+        for (expr, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            let words = asm.run(&format!("!one = 1\nstart:\nd32 {expr}"))?;
+            assert_eq!(words, &vec![(expected >> 16) as u16, expected as u16], "{expr}");
+        }
+
+        for (expr, expected) in [("1 / 0", Log::DivisionByZero), ("(1 + 2", Log::MismatchedParentheses("(1 + 2".into())), ("1 2", Log::InvalidExpression("1 2".into()))] {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, &format!("d32 {expr}")), vec![(expected, 1)]);
+        }
+
+        // displacements can be written before the parentheses
+        let mut asm = assembler::Assembler::default();
+        let words = asm.run("move.l (2 + 2) * 2(A0), D0\nmove.l (8, A0), D0\nlea 4(A1, D0.w), A0")?;
+        assert_eq!(words, &vec![0x2028, 0x0008, 0x2028, 0x0008, 0x41F1, 0x0004]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn literals() -> Result<(), Vec<logging::Diagnostic>> {
+        let data = [
+            ("$FF", 0xFFu32),
+            ("$dead_beef", 0xDEAD_BEEF),
+            ("%1010", 0b1010),
+            ("%1111_0000", 0xF0),
+            ("7 % %11", 1),
+            ("@17", 0o17),
+            ("0o777", 0o777),
+            ("1_000_000", 1_000_000),
+            ("'A'", 0x41),
+            ("'ABCD'", 0x4142_4344),
+            ("','", 0x2C),
+            ("';'", 0x3B),
+        ];
+
+        for (literal, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            let words = asm.run(&format!("d32 {literal} ; comment"))?;
+            assert_eq!(words, &vec![(expected >> 16) as u16, expected as u16], "{literal}");
+        }
+
+        let data = [
+            ("$1_0000_0000_0000_0000", Log::NumberOverflow("$1_0000_0000_0000_0000".into())),
+            ("'ABCDEFGHI'", Log::NumberOverflow("'ABCDEFGHI'".into())),
+            ("%102", Log::InvalidNumber),
+            ("$", Log::InvalidNumber),
+        ];
+
+        for (literal, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, &format!("d32 {literal}")), vec![(expected, 1)], "{literal}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn strings() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = r#"
+        d08 "Hi, \"you\"\n", 0
+        d08 '\'', "\x41\0", $42
+        pstring "abc"
+        cstring "ab;c"
+    "#;
+
+        let mut asm = assembler::Assembler::default();
+        let words = asm.run(text)?;
+
+        assert_eq!(words, &vec![
+            0x4869, 0x2C20, 0x2279, 0x6F75, 0x220A, 0x0000, // padded to an even length
+            0x2741, 0x0042,
+            0x0361, 0x6263,
+            0x6162, 0x3B63, 0x0000,
+        ]);
+
+        let data = [
+            (r#"d08 "abc"#, Log::UnterminatedString(r#""abc"#.into())),
+            (r#"d08 "\q""#, Log::InvalidEscape(r#""\q""#.into())),
+            (r#"d08 "\x4""#, Log::InvalidEscape(r#""\x4""#.into())),
+            (r#"d16 "ab""#, Log::StringNotBytes),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, text), vec![(expected, 1)], "{text}");
+        }
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(errors(&mut asm, &format!("pstring \"{}\"", "a".repeat(256))), vec![(Log::StringTooLong(256), 1)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn motorola_directives() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+    size equ 4
+    count = size / 2
+    !flag = 1
+    step set 1
+    step set step + 1
+        dc.b 1, \"A\"
+        dc.w size
+        dc.l count
+        ds.b 3
+        ds.w step
+        dcb.b 3, $FF
+        dcb.l 1, -1
+        move.w #step, D0
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        let words = asm.run(text)?;
+
+        assert_eq!(words, &vec![
+            0x0141,
+            0x0004,
+            0x0000, 0x0002,
+            0x0000, 0x0000,
+            0x0000, 0x0000,
+            0xFFFF, 0xFF00,
+            0xFFFF, 0xFFFF,
+            0x303C, 0x0002,
+        ]);
+
+        let data = [
+            ("size equ 1\nsize equ 2", Log::DefineRedefinition("size".into())),
+            ("size = 1\nsize set 2", Log::DefineRedefinition("size".into())),
+            ("dc.q 1", Log::InvalidSuffix),
+            ("ds.b 1, 2", Log::ExtraOperand("2".into())),
+            ("dcb.w 4", Log::MissingValue),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, text).into_iter().map(|e| e.0).collect::<Vec<_>>(), vec![expected], "{text}");
+        }
+
+        // large blocks are sized without building a value per element
+        let mut asm = assembler::Assembler::default();
+        let words = asm.run("ds.l $100000\nafter:\ndcb.w 2, $1234")?;
+        assert_eq!(words.len(), 0x200002);
+        assert_eq!(words[0x200000 ..], [0x1234, 0x1234]);
+        assert_eq!(asm.symbols(), vec![("after", 0x400000)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn forward_defines() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+    start:
+        move.w #size, D0
+        bra target
+        ds.b 200
+    finish:
+        nop
+    size = finish - start
+    target equ finish
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        let words = asm.run(text)?;
+
+        let mut expected = vec![0x303C, 0x00D0, 0x6000, 0x00CA];
+        expected.extend([0; 100]);
+        expected.push(0x4E71);
+        assert_eq!(words, &expected);
+
+        // struct offsets are plain numbers, never relative to the instruction
+        let text = "
+    start:
+        d32 0, 0
+    finish:
+    size = finish - start
+        move.w (size, A0), D0
+        move.w (!size, A0), D0
+        move.w (size, A0, D1.w), D0
+    ";
+
+        let expected = vec![0, 0, 0, 0, 0x3028, 0x0008, 0x3028, 0x0008, 0x3030, 0x1008];
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(asm.run(text)?, &expected);
+
+        let data = [
+            ("a = b\nb = a", vec![Log::CircularDefine("a".into()), Log::CircularDefine("b".into())]),
+            ("a = a + 1\nmove.w #a, D0", vec![Log::CircularDefine("a".into()), Log::CircularDefine("a".into())]),
+            ("a = missing", vec![Log::NoLabel("missing".into())]),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, text).into_iter().map(|e| e.0).collect::<Vec<_>>(), expected, "{text}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn alignment() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+        d08 1
+        nop
+        autopad off
+        d08 2
+        d08 3, 4
+        even
+        d08 5
+        align 8, $EE
+        cnop 2, 4
+        nop
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        let words = asm.run(text)?;
+
+        assert_eq!(words, &vec![
+            0x0100, 0x4E71,
+            0x0203, 0x0400,
+            0x05EE, 0xEEEE, 0xEEEE, 0xEEEE,
+            0x0000, 0x4E71,
+        ]);
+
+        let data = [
+            ("autopad off\nd08 1\nnop", Log::OddInstructionAddress),
+            ("align 3", Log::InvalidAlignment(3)),
+            ("align 2, 256", Log::ValueOutOfRange(256, 0, 255)),
+            ("cnop 0", Log::MissingValue),
+            ("autopad maybe", Log::InvalidArgument("maybe".into(), "on or off")),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, text).into_iter().map(|e| e.0).collect::<Vec<_>>(), vec![expected], "{text}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn instructions_68020() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+        bfextu (A0){4:8}, D1
+        bfins D2, (A1){D3:32}
+        bfffo $1234{D1:D2}, D7
+        cas.l D0, D1, (A0)
+        cas2.l D0:D1, D2:D3, (A0):(A1)
+        chk2.l (A0), D1
+        cmp2.w (A0), A1
+        muls.l D0, D2:D3
+        divu.l D0, D1
+        divsl.l D0, D2:D3
+        extb.l D0
+        link.l A6, #-8
+        unpk -(A0), -(A1), #1
+        trapeq
+        trapne.w #1
+        callm #4, (A0)
+        rtm A2
+    ";
+
+        let mut asm = assembler::Assembler::default();
+        asm.cpu_type = assembler::CpuType::MC68020;
+        let words = asm.run(text)?;
+
+        assert_eq!(words, &vec![
+            0xE9D0, 0x1108,
+            0xEFD1, 0x28C0,
+            0xEDF8, 0x7862, 0x1234,
+            0x0ED0, 0x0040,
+            0x0EFC, 0x8080, 0x90C1,
+            0x04D0, 0x1800,
+            0x02D0, 0x9000,
+            0x4C00, 0x3C02,
+            0x4C40, 0x1001,
+            0x4C40, 0x3802,
+            0x49C0,
+            0x480E, 0xFFFF, 0xFFF8,
+            0x8388, 0x0001,
+            0x57FC,
+            0x56FA, 0x0001,
+            0x06D0, 0x0004,
+            0x06CA,
+        ]);
+
+        let data = [
+            ("bfextu (A0){32:8}, D1", Log::ValueOutOfRange(32, 0, 31)),
+            ("bfset (A0)+{0:8}", Log::InvalidAddressingMode),
+            ("bfclr (A0)", Log::MissingBitfield("(A0)".into())),
+            ("mulu.w D0, D1:D2", Log::InvalidAddressingMode),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            asm.cpu_type = assembler::CpuType::MC68020;
+            assert_eq!(errors(&mut asm, text).into_iter().map(|e| e.0).collect::<Vec<_>>(), vec![expected], "{text}");
+        }
+
+        let data = [
+            ("bftst D0{0:32}", Log::UnsupportedInstruction),
+            ("extb.l D0", Log::UnsupportedInstruction),
+            ("mulu.l D0, D1", Log::CpuTypeSizeNotValid),
+            ("link.l A6, #-8", Log::CpuTypeSizeNotValid),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, text).into_iter().map(|e| e.0).collect::<Vec<_>>(), vec![expected], "{text}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn cpu_targets() -> Result<(), Vec<logging::Diagnostic>> {
+        let data = [
+            (assembler::CpuType::MC68030, "
+            pflusha
+            pflush D2, #0, (A0)
+            ploadr DFC, (A1)
+            pmove TC, (A0)
+            pmovefd (A0), SRP
+            ptestr #1, (A0), #7
+            movec CACR, D0
+        ", vec![0xF000, 0x2400, 0xF010, 0x380A, 0xF011, 0x2201, 0xF010, 0x4200, 0xF010, 0x4900, 0xF010, 0x9E11, 0x4E7A, 0x0002]),
+
+            (assembler::CpuType::MC68040, "
+            pflush (A0)
+            pflusha
+            ptestw (A3)
+            cinva BC
+            cpushp DC, (A1)
+            move16 (A0)+, (A1)+
+            move16 $12345678, (A5)
+            movec D0, URP
+        ", vec![0xF508, 0xF518, 0xF54B, 0xF4D8, 0xF471, 0xF620, 0x9000, 0xF61D, 0x1234, 0x5678, 0x4E7B, 0x0806]),
+
+            (assembler::CpuType::MC68060, "
+            movec PCR, D0
+            lpstop #$2000
+        ", vec![0x4E7A, 0x0808, 0xF800, 0x01C0, 0x2000]),
+
+            (assembler::CpuType::CPU32, "
+            bgnd
+            tblu.w (A0), D1
+            tbls.b D2:D3, D4
+            move.l (A0, D0.w*4), D1
+        ", vec![0x4AFA, 0xF810, 0x1140, 0xF802, 0x4803, 0x2230, 0x0400]),
+        ];
+
+        for (cpu_type, text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            asm.cpu_type = cpu_type;
+            assert_eq!(asm.run(text)?, &expected, "{text}");
+        }
+
+        let data = [
+            (assembler::CpuType::MC68030, "move16 (A0)+, (A1)+", Log::UnsupportedInstruction),
+            (assembler::CpuType::MC68030, "callm #1, (A0)", Log::UnsupportedInstruction),
+            (assembler::CpuType::MC68030, "movec TC, D0", Log::UnsupportedControlRegister),
+            (assembler::CpuType::MC68030, "pmove.l CRP, (A0)", Log::SizeOperandMismatch),
+            (assembler::CpuType::MC68040, "pmove TC, (A0)", Log::UnsupportedInstruction),
+            (assembler::CpuType::MC68060, "ptestr (A0)", Log::UnsupportedInstruction),
+            (assembler::CpuType::CPU32, "bfextu D0{0:8}, D1", Log::UnsupportedInstruction),
+            (assembler::CpuType::CPU32, "move.l ([A0]), D0", Log::CpuTypeModeNotValid),
+            (assembler::CpuType::MC68010, "movec CACR, D0", Log::UnsupportedControlRegister),
+        ];
+
+        for (cpu_type, text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            asm.cpu_type = cpu_type;
+            assert_eq!(errors(&mut asm, text).into_iter().map(|e| e.0).collect::<Vec<_>>(), vec![expected], "{text}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn fpu_instructions() -> Result<(), Vec<logging::Diagnostic>> {
+        let data = [
+            ("fadd.x FP1, FP2",             vec![0xF200, 0x0522]),
+            ("fadd.l D0, FP3",              vec![0xF200, 0x41A2]),
+            ("fadd.s #1.5, FP0",            vec![0xF23C, 0x4422, 0x3FC0, 0x0000]),
+            ("fsqrt.d (A0), FP4",           vec![0xF210, 0x5604]),
+            ("fabs FP1",                    vec![0xF200, 0x0498]),
+            ("ftst.x FP3",                  vec![0xF200, 0x0C3A]),
+            ("fsincos.x FP0, FP1:FP2",      vec![0xF200, 0x0131]),
+            ("fmove.x FP2, (A1)+",          vec![0xF219, 0x6900]),
+            ("fmove.p FP0, -(A2)",          vec![0xF222, 0x6C11]),
+            ("fmove.l FPCR, D0",            vec![0xF200, 0xB000]),
+            ("fmove.l #0, FPCR",            vec![0xF23C, 0x9000, 0x0000, 0x0000]),
+            ("fmovem.l FPCR/FPSR, -(A7)",   vec![0xF227, 0xB800]),
+            ("fmovem FP0-FP3, -(A7)",       vec![0xF227, 0xE00F]),
+            ("fmovem (A7)+, FP0-FP3",       vec![0xF21F, 0xD0F0]),
+            ("fmovem D1, (A0)",             vec![0xF210, 0xF810]),
+            ("fmovecr #$0F, FP1",           vec![0xF200, 0x5C8F]),
+            ("l:\nfbeq l",                  vec![0xF281, 0xFFFE]),
+            ("l:\nfdbgt D3, l",             vec![0xF24B, 0x0012, 0xFFFC]),
+            ("fsne (A0)",                   vec![0xF250, 0x000E]),
+            ("ftrapne.w #1",                vec![0xF27A, 0x000E, 0x0001]),
+            ("fnop",                        vec![0xF280, 0x0000]),
+            ("fsave -(A7)",                 vec![0xF327]),
+            ("dc.s -2.5e1",                 vec![0xC1C8, 0x0000]),
+            ("dc.d 1.0",                    vec![0x3FF0, 0x0000, 0x0000, 0x0000]),
+            ("dc.x 1.0",                    vec![0x3FFF, 0x0000, 0x8000, 0x0000, 0x0000, 0x0000]),
+            ("dc.p 1.5",                    vec![0x0000, 0x0001, 0x5000, 0x0000, 0x0000, 0x0000]),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            asm.cpu_type = assembler::CpuType::MC68020;
+            asm.fpu = true;
+            assert_eq!(asm.run(text)?, &expected, "{text}");
+        }
+
+        let data = [
+            (assembler::CpuType::MC68020, false, "fadd FP0, FP1",       Log::FpuDisabled),
+            (assembler::CpuType::MC68000, true,  "fadd FP0, FP1",       Log::UnsupportedInstruction),
+            (assembler::CpuType::MC68020, true,  "fadd.d D0, FP0",      Log::SizeOperandMismatch),
+            (assembler::CpuType::MC68020, true,  "fmove.w #1, FPCR",    Log::SizeOperandMismatch),
+            (assembler::CpuType::MC68000, false, "move.l #1.5, D0",     Log::UnexpectedFloat),
+            (assembler::CpuType::MC68000, false, "dc.s 1e39",           Log::FloatOutOfRange(".s", f32::MAX as f64)),
+            (assembler::CpuType::MC68000, false, "dc.d -1e309",         Log::FloatOutOfRange(".d", f64::MAX)),
+            (assembler::CpuType::MC68020, true,  "fmove.x #1e400, FP0", Log::FloatOutOfRange(".x", f64::MAX)),
+        ];
+
+        for (cpu_type, fpu, text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            asm.cpu_type = cpu_type;
+            asm.fpu = fpu;
+            assert_eq!(errors(&mut asm, text).into_iter().map(|e| e.0).collect::<Vec<_>>(), vec![expected], "{text}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn coldfire_targets() -> Result<(), Vec<logging::Diagnostic>> {
+        let data = [
+            (assembler::CpuType::ColdFireA, "
+            add.l D0, D1
+            lsl.l #2, D3
+            move.l (A0, D0.l*4), D1
+            move.l 4(A0), 8(A1)
+            movem.l D0-D3/A0, (A7)
+            rems.l (A0), D1:D2
+            remu.l D3, D4:D5
+            move.w #$2700, SR
+            movec A0, VBR
+            tpf
+        ", vec![0xD280, 0xE58B, 0x2230, 0x0C00, 0x2368, 0x0004, 0x0008, 0x48D7, 0x010F, 0x4C50, 0x2801, 0x4C43, 0x5004,
+                    0x46FC, 0x2700, 0x4E7B, 0x8801, 0x51FC]),
+
+            (assembler::CpuType::ColdFireA, "
+            mac.w D1.u, A2.l<<, ACC1
+            mac.l D0, D1
+            msac.w D0.l, D1.u>>, ACC3
+            mac.w D1.u, D2.l, (A0)+&, D3, ACC0
+            mac.l A1, D7, -(A3), A4, ACC2
+            move.l ACC0, D0
+            move.l #0, ACC1
+            move.l D2, MACSR
+            move.l MACSR, CCR
+            move.l ACC1, ACC2
+            movclr.l ACC3, D1
+        ", vec![0xA4C1, 0x0240, 0xA200, 0x0800, 0xA280, 0x0790, 0xA698, 0x2061, 0xA8E3, 0x7819, 0xA180, 0xA33C, 0x0000, 0x0000,
+                    0xA902, 0xA9C0, 0xA511, 0xA7C1]),
+
+            (assembler::CpuType::ColdFireB, "
+            mov3q.l #-1, D0
+            mov3q.l #7, (A0)
+            mvs.b (A0), D1
+            mvz.w D2, D3
+            sats.l D4
+            cmp.b D0, D1
+            move.b #1, 4(A0)
+        ", vec![0xA140, 0xAF50, 0x7310, 0x77C2, 0x4C84, 0xB200, 0x117C, 0x0001, 0x0004]),
+
+            (assembler::CpuType::ColdFireC, "
+            bitrev.l D0
+            ff1.l D2
+        ", vec![0x00C0, 0x04C2]),
+        ];
+
+        for (cpu_type, text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            asm.cpu_type = cpu_type;
+            assert_eq!(asm.run(text)?, &expected, "{text}");
+        }
+
+        let data = [
+            (assembler::CpuType::ColdFireA, "add.w D0, D1", Log::CpuTypeSizeNotValid),
+            (assembler::CpuType::ColdFireA, "cmp.b D0, D1", Log::CpuTypeSizeNotValid),
+            (assembler::CpuType::ColdFireA, "l:\nbra.l l", Log::CpuTypeSizeNotValid),
+            (assembler::CpuType::ColdFireA, "abcd D0, D1", Log::UnsupportedInstruction),
+            (assembler::CpuType::ColdFireA, "mov3q.l #1, D0", Log::UnsupportedInstruction),
+            (assembler::CpuType::ColdFireA, "move.l #1, 4(A0)", Log::CpuTypeModeNotValid),
+            (assembler::CpuType::ColdFireA, "move.l 4(A0), (A1, D0.l)", Log::CpuTypeModeNotValid),
+            (assembler::CpuType::ColdFireA, "move.l (A0, D0.w), D1", Log::CpuTypeModeNotValid),
+            (assembler::CpuType::ColdFireA, "move.l ([A0]), D1", Log::CpuTypeModeNotValid),
+            (assembler::CpuType::ColdFireA, "movem.l D0-D1, -(A7)", Log::CpuTypeModeNotValid),
+            (assembler::CpuType::ColdFireA, "andi.l #1, (A0)", Log::CpuTypeModeNotValid),
+            (assembler::CpuType::ColdFireA, "mulu.l D0, D1:D2", Log::CpuTypeModeNotValid),
+            (assembler::CpuType::ColdFireA, "movec CACR, D0", Log::CpuTypeModeNotValid),
+            (assembler::CpuType::ColdFireA, "mac.l D0.u, D1", Log::SizeOperandMismatch),
+            (assembler::CpuType::ColdFireB, "mov3q.l #0, D0", Log::QuickImmediateOutOfRange(0, -1, 7)),
+            (assembler::CpuType::ColdFireB, "bitrev.l D0", Log::UnsupportedInstruction),
+            (assembler::CpuType::MC68020, "mac.w D0, D1", Log::UnsupportedInstruction),
+        ];
+
+        for (cpu_type, text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            asm.cpu_type = cpu_type;
+            assert_eq!(errors(&mut asm, text).into_iter().map(|e| e.0).collect::<Vec<_>>(), vec![expected], "{text}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn dbcc_displacement() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+    start:
+        dbf D0, start
+        nop
+    wait:
+        dbne D2, wait
+    ";
+
+        let expected = vec![0x51C8, 0xFFFE, 0x4E71, 0x56CA, 0xFFFE];
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(asm.run(text)?, &expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pc_index_size() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+    start:
+        lea (start, PC, D1.w), A0
+        lea (start, PC, D1.l), A0
+        lea (start, PC, A2.w), A0
+    ";
+
+        let expected = vec![0x41FB, 0x10FE, 0x41FB, 0x18FA, 0x41FB, 0xA0F6];
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(asm.run(text)?, &expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tst_and_movep() -> Result<(), Vec<logging::Diagnostic>> {
+        for text in ["tst.w A0", "tst.w #1", "tst.w (0, PC)"] {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, text), vec![(Log::CpuTypeModeNotValid, 1)], "{text}");
+        }
+
+        let text = "
+        tst.w A0
+        tst.l #1
+        movep.w D0, (4, A1)
+        movep.l (0, A0), D1
+    ";
+
+        let expected = vec![0x4A48, 0x4ABC, 0x0000, 0x0001, 0x0189, 0x0004, 0x0348, 0x0000];
+
+        let mut asm = assembler::Assembler::default();
+        asm.cpu_type = assembler::CpuType::MC68020;
+        assert_eq!(asm.run(text)?, &expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn jumps() -> Result<(), Vec<logging::Diagnostic>> {
+        let data = [
+            ("jsr (A0)",        vec![0x4E90]),
+            ("jmp (A0)",        vec![0x4ED0]),
+            ("jsr (8, A1)",     vec![0x4EA9, 0x0008]),
+            ("jmp (8, A1)",     vec![0x4EE9, 0x0008]),
+            ("jsr 0x12345678",  vec![0x4EB9, 0x1234, 0x5678]),
+            ("jmp 0x12345678",  vec![0x4EF9, 0x1234, 0x5678]),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(asm.run(text)?, &expected, "{text}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn usp_moves() -> Result<(), Vec<logging::Diagnostic>> {
+        let data = [
+            ("move.l A0, USP",  vec![0x4E60]),
+            ("move.l A5, USP",  vec![0x4E65]),
+            ("move.l USP, A0",  vec![0x4E68]),
+            ("move.l USP, A6",  vec![0x4E6E]),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(asm.run(text)?, &expected, "{text}");
+        }
+
+        for text in ["move.l D0, USP", "move.l USP, D0"] {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, text), vec![(Log::InvalidAddressingMode, 1)], "{text}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn label_differences() -> Result<(), Vec<logging::Diagnostic>> {
+        let text = "
+    start:
+        nop
+        nop
+    end:
+        move.w (end-start, A0), D0
+        move.w (end-start, A0, D1.w), D0
+        lea (end, PC), A1
+        lea (end+2-start, PC), A1
+    ";
+
+        let expected = vec![0x4E71, 0x4E71, 0x3028, 0x0004, 0x3030, 0x1004, 0x43FA, 0xFFF6, 0x43FA, 0x0006];
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(asm.run(text)?, &expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn address_overflow() -> Result<(), Vec<logging::Diagnostic>> {
+        let data = [
+            ("rorg $FFFFFFFE\nnop\nnop", vec![(Log::AddressOutOfRange, 2), (Log::AddressOutOfRange, 3)]),
+            ("rorg $FFFFFFF0\nlea ($FFFF0000).l, A0\nbra.w start\nstart:\nmove.l #1, D0\nnop", vec![(Log::AddressOutOfRange, 5)]),
+            ("rorg $FFFFFFFE\nd16 1, 2", vec![(Log::AddressOutOfRange, 2)]),
+            ("rorg $FFFFFFFE\nds.w $7FFFFFFF", vec![(Log::AddressOutOfRange, 2)]),
+            // the branch only grows once the label is known, moving the nop past the end
+            ("rorg $FFFFFF70\nbra end\nds.b $8A\nend:\nnop", vec![(Log::AddressOutOfRange, 5)]),
+            ("rorg $FFFFFF00\nbra end\nbra end\nbra end\nds.b $F2\nend:\nmove.l #1, D0", vec![(Log::AddressOutOfRange, 7)]),
+        ];
+
+        for (text, expected) in data {
+            let mut asm = assembler::Assembler::default();
+            assert_eq!(errors(&mut asm, text), expected, "{text}");
+        }
+
+        let mut asm = assembler::Assembler::default();
+        assert_eq!(asm.run("rorg $FFFFFFFA\nnop\nnop")?, &vec![0x4E71, 0x4E71]);
+
+        Ok(())
+    }
 }